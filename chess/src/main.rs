@@ -1,16 +1,24 @@
 extern crate chesslib;
-use chesslib::handle_uci_command;
-use std::fs::OpenOptions;
+use chesslib::logger::LOG_FILE_ENV;
+use chesslib::uci::UciEngine;
+use std::env;
 use std::io::{self, BufRead, Write};
+use std::sync::mpsc;
+use std::thread;
 
-fn log_to_file(message: &str, append: bool) {
-    let mut file = OpenOptions::new()
-        .create(true)
-        .write(true) // Ensure write mode is enabled
-        .append(append) // Append if true, overwrite otherwise
-        .open("/home/dgrant/git_personal/rust/chess/engine.log")
-        .expect("Failed to open log file");
-    writeln!(file, "{}", message).expect("Failed to write to log file");
+// File to log the UCI conversation to: `--log <file>` or `--log=<file>` on the command line,
+// or else the environment variable
+fn log_file() -> Option<String> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--log" {
+            return args.next();
+        }
+        if let Some(path) = arg.strip_prefix("--log=") {
+            return Some(path.to_string());
+        }
+    }
+    env::var(LOG_FILE_ENV).ok().filter(|path| !path.is_empty())
 }
 
 fn main() {
@@ -18,26 +26,49 @@ fn main() {
     let mut input = String::new();
 
     println!("Chess engine ready. Waiting for UCI commands...");
-    log_to_file("======", false);
-    loop {
-        input.clear();
-        if stdin.lock().read_line(&mut input).is_err() {
-            eprintln!("Error reading input");
-            continue;
+
+    // The search runs on its own thread and sends its info and bestmove lines here
+    let (search_output, search_lines) = mpsc::channel::<String>();
+    let mut engine = UciEngine::new();
+    engine.set_search_output(Some(search_output));
+    let printer = thread::spawn(move || {
+        for line in search_lines {
+            println!("{}", line);
+            io::stdout().flush().unwrap();
         }
+    });
 
-        log_to_file(&format!("Received: {}", input.trim()), true);
+    if let Some(path) = log_file() {
+        if let Err(message) = engine.set_log_file(&path) {
+            eprintln!("{}", message);
+        }
+    }
 
-        let response = handle_uci_command(&input);
-        log_to_file(&format!("Responded: {}", response), true);
+    loop {
+        input.clear();
+        let command = match stdin.lock().read_line(&mut input) {
+            Ok(0) => "quit", // End of input
+            Ok(_) => input.trim(),
+            Err(_) => {
+                eprintln!("Error reading input");
+                continue;
+            }
+        };
 
-        if input.trim() == "quit" {
+        let response = engine.handle(command);
+        if command == "quit" {
             break; // Exit on "quit" command
         }
 
-        println!("{}", response);
+        for line in response {
+            println!("{}", line);
+        }
         io::stdout().flush().unwrap();
     }
 
+    // Dropping the engine and its sender lets the printer finish once the last search line is out
+    drop(engine);
+    printer.join().unwrap();
+
     println!("Exiting chess engine.");
 }
@@ -0,0 +1,36 @@
+//! Counts the nodes the search visits with and without move ordering: MVV-LVA captures,
+//! killers and history in the main search against trying only the hash move first.
+//!
+//! Run with `cargo bench --bench move_ordering`.
+extern crate chesslib;
+use chesslib::board::Board;
+use chesslib::search::Searcher;
+use std::time::Instant;
+
+const POSITIONS: [(&str, &str, u32); 4] = [
+    ("start", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 6),
+    ("italian", "r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4", 5),
+    ("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 4),
+    ("endgame", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 8),
+];
+
+fn search(board: &Board, depth: u32, ordered: bool) -> (u64, f64) {
+    let mut searcher = Searcher::with_hash_size(16);
+    searcher.set_move_ordering(ordered);
+    let start = Instant::now();
+    let result = searcher.search(board, depth, |_| {});
+    (result.nodes, start.elapsed().as_secs_f64())
+}
+
+fn main() {
+    println!("{:<10} {:>5} {:>12} {:>12} {:>10}", "position", "depth", "unordered", "ordered", "reduction");
+    for (name, fen, depth) in POSITIONS {
+        let board = Board::from_fen(fen).unwrap();
+        let (unordered, unordered_secs) = search(&board, depth, false);
+        let (ordered, ordered_secs) = search(&board, depth, true);
+        println!("{:<10} {:>5} {:>12} {:>12} {:>9.1}%   ({:.2}s -> {:.2}s)",
+            name, depth, unordered, ordered,
+            100.0 * (1.0 - ordered as f64 / unordered as f64),
+            unordered_secs, ordered_secs);
+    }
+}
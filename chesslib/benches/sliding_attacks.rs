@@ -0,0 +1,50 @@
+//! Compares the magic bitboard lookups against walking the rays square by square.
+//!
+//! Run with `cargo bench --bench sliding_attacks`.
+extern crate chesslib;
+use chesslib::board::Board;
+use chesslib::move_generation::{bishop_attacks, bishop_ray_attacks, rook_attacks, rook_ray_attacks};
+use rand::{Rng, SeedableRng};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ROUNDS: usize = 200;
+
+fn time_attacks(occupancies: &[u64], attacks: impl Fn(u8, u64) -> u64) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for &occupied in occupancies {
+            for square in 0..64u8 {
+                black_box(attacks(black_box(square), black_box(occupied)));
+            }
+        }
+    }
+    start.elapsed()
+}
+
+fn report(name: &str, lookups: usize, ray: Duration, magic: Duration) {
+    println!("{:<8} ray walking {:>8.2} ns/lookup   magic {:>6.2} ns/lookup   speedup {:.1}x",
+        name,
+        ray.as_nanos() as f64 / lookups as f64,
+        magic.as_nanos() as f64 / lookups as f64,
+        ray.as_secs_f64() / magic.as_secs_f64());
+}
+
+fn main() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    let occupancies: Vec<u64> = (0..1000).map(|_| rng.gen::<u64>() & rng.gen::<u64>()).collect();
+    let lookups = ROUNDS * occupancies.len() * 64;
+
+    // Build the tables before timing anything
+    black_box(bishop_attacks(0, 0) | rook_attacks(0, 0));
+
+    report("bishop", lookups, time_attacks(&occupancies, bishop_ray_attacks), time_attacks(&occupancies, bishop_attacks));
+    report("rook", lookups, time_attacks(&occupancies, rook_ray_attacks), time_attacks(&occupancies, rook_attacks));
+
+    let kiwipete = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    let start = Instant::now();
+    let nodes = kiwipete.perft(4);
+    let elapsed = start.elapsed();
+    println!("perft(4) of kiwipete: {} nodes in {:.2?} ({:.1} Mnps)",
+        nodes, elapsed, nodes as f64 / elapsed.as_secs_f64() / 1e6);
+}
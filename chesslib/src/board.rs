@@ -9,15 +9,93 @@ pub static G: &'static str = "g";
 pub static H: &'static str = "h";
 
 use crate::types::Square;
+use crate::zobrist;
+
+/// FEN of the standard starting position
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// Castling rights flags, combined in `Board::castling_rights`
+pub const WHITE_KINGSIDE: u8 = 1;
+pub const WHITE_QUEENSIDE: u8 = 2;
+pub const BLACK_KINGSIDE: u8 = 4;
+pub const BLACK_QUEENSIDE: u8 = 8;
+
+/// Describes the squares involved in one of the four castling moves
+struct Castling {
+    right: u8,
+    king_src: Square,
+    king_target: Square,
+    rook_src: Square,
+    rook_target: Square,
+    /// Squares between king and rook that must be empty
+    must_be_empty: u64,
+    /// Squares the king starts on, passes through or lands on, which must not be attacked
+    must_be_safe: [Square; 3],
+}
+
+const CASTLINGS: [Castling; 4] = [
+    Castling {
+        right: WHITE_KINGSIDE,
+        king_src: Square::E1, king_target: Square::G1,
+        rook_src: Square::H1, rook_target: Square::F1,
+        must_be_empty: (1 << 5) | (1 << 6),
+        must_be_safe: [Square::E1, Square::F1, Square::G1],
+    },
+    Castling {
+        right: WHITE_QUEENSIDE,
+        king_src: Square::E1, king_target: Square::C1,
+        rook_src: Square::A1, rook_target: Square::D1,
+        must_be_empty: (1 << 1) | (1 << 2) | (1 << 3),
+        must_be_safe: [Square::E1, Square::D1, Square::C1],
+    },
+    Castling {
+        right: BLACK_KINGSIDE,
+        king_src: Square::E8, king_target: Square::G8,
+        rook_src: Square::H8, rook_target: Square::F8,
+        must_be_empty: (1 << 61) | (1 << 62),
+        must_be_safe: [Square::E8, Square::F8, Square::G8],
+    },
+    Castling {
+        right: BLACK_QUEENSIDE,
+        king_src: Square::E8, king_target: Square::C8,
+        rook_src: Square::A8, rook_target: Square::D8,
+        must_be_empty: (1 << 57) | (1 << 58) | (1 << 59),
+        must_be_safe: [Square::E8, Square::D8, Square::C8],
+    },
+];
+
+/// Castling rights that are lost once a piece moves from or to the given square,
+/// i.e. when a king or rook leaves its home square or a rook is captured on it
+fn castling_rights_lost(square: Square) -> u8 {
+    match square {
+        Square::E1 => WHITE_KINGSIDE | WHITE_QUEENSIDE,
+        Square::H1 => WHITE_KINGSIDE,
+        Square::A1 => WHITE_QUEENSIDE,
+        Square::E8 => BLACK_KINGSIDE | BLACK_QUEENSIDE,
+        Square::H8 => BLACK_KINGSIDE,
+        Square::A8 => BLACK_QUEENSIDE,
+        _ => 0,
+    }
+}
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 #[derive(PartialEq)]
 pub enum Color {
     White,
     Black,
 }
 
-#[derive(PartialEq)]
+impl Color {
+    /// Returns the other side
+    pub fn opposite(&self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum PieceType {
     Pawn,
     Rook,
@@ -27,7 +105,35 @@ pub enum PieceType {
     King,
 }
 
-#[derive(Debug)]
+/// Piece types a pawn can promote to, strongest first
+pub const PROMOTION_PIECES: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+impl PieceType {
+    /// Parses the lowercase promotion suffix of a UCI move, e.g. the `q` in `e7e8q`
+    pub fn from_promotion_char(c: char) -> Option<PieceType> {
+        match c {
+            'q' => Some(PieceType::Queen),
+            'r' => Some(PieceType::Rook),
+            'b' => Some(PieceType::Bishop),
+            'n' => Some(PieceType::Knight),
+            _ => None,
+        }
+    }
+
+    /// Returns the lowercase promotion suffix used in UCI moves
+    pub fn to_promotion_char(&self) -> char {
+        match self {
+            PieceType::Pawn => 'p',
+            PieceType::Rook => 'r',
+            PieceType::Knight => 'n',
+            PieceType::Bishop => 'b',
+            PieceType::Queen => 'q',
+            PieceType::King => 'k',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 #[derive(PartialEq)]
 pub enum Piece {
     WhitePawn,
@@ -62,6 +168,36 @@ impl Piece {
         }
     }
 
+    /// Returns the piece of the given type and color
+    pub fn new(piece_type: PieceType, color: Color) -> Piece {
+        match (color, piece_type) {
+            (Color::White, PieceType::Pawn) => Piece::WhitePawn,
+            (Color::White, PieceType::Rook) => Piece::WhiteRook,
+            (Color::White, PieceType::Knight) => Piece::WhiteKnight,
+            (Color::White, PieceType::Bishop) => Piece::WhiteBishop,
+            (Color::White, PieceType::Queen) => Piece::WhiteQueen,
+            (Color::White, PieceType::King) => Piece::WhiteKing,
+            (Color::Black, PieceType::Pawn) => Piece::BlackPawn,
+            (Color::Black, PieceType::Rook) => Piece::BlackRook,
+            (Color::Black, PieceType::Knight) => Piece::BlackKnight,
+            (Color::Black, PieceType::Bishop) => Piece::BlackBishop,
+            (Color::Black, PieceType::Queen) => Piece::BlackQueen,
+            (Color::Black, PieceType::King) => Piece::BlackKing,
+        }
+    }
+
+    /// Returns the kind of piece regardless of its color
+    pub fn piece_type(&self) -> PieceType {
+        match self {
+            Piece::WhitePawn | Piece::BlackPawn => PieceType::Pawn,
+            Piece::WhiteRook | Piece::BlackRook => PieceType::Rook,
+            Piece::WhiteKnight | Piece::BlackKnight => PieceType::Knight,
+            Piece::WhiteBishop | Piece::BlackBishop => PieceType::Bishop,
+            Piece::WhiteQueen | Piece::BlackQueen => PieceType::Queen,
+            Piece::WhiteKing | Piece::BlackKing => PieceType::King,
+        }
+    }
+
     pub fn color(&self) -> Color {
         match self {
             Piece::WhitePawn | Piece::WhiteRook | Piece::WhiteKnight |
@@ -69,31 +205,139 @@ impl Piece {
             _ => Color::Black
         }
     }
+
+    /// Returns the FEN letter of the piece, uppercase for white and lowercase for black
+    pub fn to_fen_char(&self) -> char {
+        match self {
+            Piece::WhitePawn => 'P',
+            Piece::WhiteRook => 'R',
+            Piece::WhiteKnight => 'N',
+            Piece::WhiteBishop => 'B',
+            Piece::WhiteQueen => 'Q',
+            Piece::WhiteKing => 'K',
+            Piece::BlackPawn => 'p',
+            Piece::BlackRook => 'r',
+            Piece::BlackKnight => 'n',
+            Piece::BlackBishop => 'b',
+            Piece::BlackQueen => 'q',
+            Piece::BlackKing => 'k',
+        }
+    }
+
+    /// Parses a piece from its FEN letter, uppercase for white and lowercase for black
+    pub fn from_fen_char(c: char) -> Option<Piece> {
+        match c {
+            'P' => Some(Piece::WhitePawn),
+            'R' => Some(Piece::WhiteRook),
+            'N' => Some(Piece::WhiteKnight),
+            'B' => Some(Piece::WhiteBishop),
+            'Q' => Some(Piece::WhiteQueen),
+            'K' => Some(Piece::WhiteKing),
+            'p' => Some(Piece::BlackPawn),
+            'r' => Some(Piece::BlackRook),
+            'n' => Some(Piece::BlackKnight),
+            'b' => Some(Piece::BlackBishop),
+            'q' => Some(Piece::BlackQueen),
+            'k' => Some(Piece::BlackKing),
+            _ => None,
+        }
+    }
 }
 
+/// How a finished game ended
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GameResult {
+    Checkmate { winner: Color },
+    Stalemate,
+    /// Fifty moves by each side without a capture or a pawn move
+    FiftyMoveRule,
+    /// The same position for the third time, with the same side to move
+    ThreefoldRepetition,
+}
+
+impl GameResult {
+    /// Returns the winning side, or `None` for a draw
+    pub fn winner(&self) -> Option<Color> {
+        match self {
+            GameResult::Checkmate { winner } => Some(*winner),
+            GameResult::Stalemate | GameResult::FiftyMoveRule | GameResult::ThreefoldRepetition => None,
+        }
+    }
+}
+
+/// Halfmove clock at which the fifty-move rule draws the game
+pub const FIFTY_MOVE_LIMIT: u32 = 100;
+
+/// UCI notation for "no move", sent as the best move when the game is already over
+pub const NULL_MOVE: &str = "0000";
+
+// First and eighth ranks, where pawns promote
+const LAST_RANKS: u64 = 0xFF000000000000FF;
+
 // Define a Move struct using the Square enum.
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Move {
     pub src: Square,
     pub target: Square,
+    /// Piece a pawn reaching the last rank turns into
+    pub promotion: Option<PieceType>,
 }
 
+impl Move {
+    pub fn new(src: Square, target: Square) -> Move {
+        Move { src, target, promotion: None }
+    }
+}
 
 // Example conversion of a move string into a Move struct.
 impl TryFrom<&str> for Move {
     type Error = &'static str;
     fn try_from(mv: &str) -> Result<Self, Self::Error> {
-        if mv.len() != 4 {
+        if mv.len() != 4 && mv.len() != 5 {
             return Err("Invalid move format");
         }
-        let src = Square::try_from(&mv[0..2])?;
-        let target = Square::try_from(&mv[2..4])?;
-        Ok(Move { src, target })
+        let src = Square::try_from(mv.get(0..2).ok_or("Invalid move format")?)?;
+        let target = Square::try_from(mv.get(2..4).ok_or("Invalid move format")?)?;
+        let promotion = match mv.chars().nth(4) {
+            Some(c) => Some(PieceType::from_promotion_char(c).ok_or("Invalid promotion piece")?),
+            None => None,
+        };
+        Ok(Move { src, target, promotion })
+    }
+}
+
+/// Formats the move in UCI long algebraic notation, e.g. `e2e4` or `e7e8q`
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}", self.src, self.target)?;
+        if let Some(promotion) = self.promotion {
+            write!(f, "{}", promotion.to_promotion_char())?;
+        }
+        Ok(())
     }
 }
 
 
-#[derive(Debug)]
+/// What `Board::make_move` changed, so `Board::unmake_move` can put it back
+///
+/// Unmaking consumes it, handing any history it carries back to the board without a copy.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Undo {
+    pub mv: Move,
+    /// Piece that made the move, a pawn for promotions
+    pub moved: Piece,
+    /// Piece taken by the move, including a pawn taken en passant
+    pub captured: Option<Piece>,
+    pub castling_rights: u8,
+    pub en_passant: Option<Square>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    pub hash: u64,
+    /// Position history the move cleared, if it was a capture or pawn move
+    pub history: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Board {
     /// White pieces
     pub white_pawns: u64,
@@ -115,11 +359,214 @@ pub struct Board {
     pub any_white: u64,
     pub any_black: u64,
     pub empty: u64,
-    pub side_to_move: Color
+    pub side_to_move: Color,
+
+    /// Castling availability as a combination of the castling rights flags
+    pub castling_rights: u8,
+    /// Square behind a pawn that just made a double push, if any
+    pub en_passant: Option<Square>,
+    /// Halfmoves since the last capture or pawn move
+    halfmove_clock: u32,
+    /// Number of the current full move, starting at 1 and incremented after Black moves
+    fullmove_number: u32,
+    /// Zobrist key of the position, kept up to date by `apply_move`
+    pub hash: u64,
+    /// Keys of the positions since the last capture or pawn move, oldest first, leaving out
+    /// the current one, to spot repetitions
+    pub history: Vec<u64>,
 }
 
 impl Board {
 
+    /// Creates a board from a position in Forsyth–Edwards Notation (FEN)
+    ///
+    /// The halfmove clock and fullmove number fields are optional and default to `0` and `1`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem if the FEN is malformed, for example a rank that
+    /// does not cover exactly eight squares, an unknown piece letter, or a side missing its king.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chesslib::board::{Board, Color};
+    /// let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+    ///
+    /// assert_eq!(board.get_piece_at_coordinate("e4"), "♙");
+    /// assert_eq!(board.side_to_move, Color::Black);
+    /// assert!(Board::from_fen("not a fen").is_err());
+    /// ```
+    pub fn from_fen(fen: &str) -> Result<Board, &'static str> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 || fields.len() > 6 {
+            return Err("FEN must have between 4 and 6 fields");
+        }
+
+        let mut board = Board {
+            white_pawns: 0,
+            white_knights: 0,
+            white_bishops: 0,
+            white_rooks: 0,
+            white_queen: 0,
+            white_king: 0,
+            black_pawns: 0,
+            black_knights: 0,
+            black_bishops: 0,
+            black_rooks: 0,
+            black_queen: 0,
+            black_king: 0,
+            any_white: 0,
+            any_black: 0,
+            empty: 0,
+            side_to_move: Color::White,
+            castling_rights: 0,
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+            history: Vec::new(),
+        };
+
+        // Piece placement, from rank 8 down to rank 1
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err("FEN piece placement must have 8 ranks");
+        }
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let rank = 7 - i as u8;
+            let mut file = 0u8;
+            for c in rank_str.chars() {
+                if let Some(empty_squares) = c.to_digit(10) {
+                    if empty_squares == 0 || empty_squares > 8 {
+                        return Err("Invalid empty square count in FEN");
+                    }
+                    file += empty_squares as u8;
+                } else {
+                    let piece = Piece::from_fen_char(c).ok_or("Invalid piece letter in FEN")?;
+                    if file >= 8 {
+                        return Err("FEN rank has more than 8 squares");
+                    }
+                    *board.piece_bitboard_mut(&piece) |= 1u64 << (rank * 8 + file);
+                    file += 1;
+                }
+                if file > 8 {
+                    return Err("FEN rank has more than 8 squares");
+                }
+            }
+            if file != 8 {
+                return Err("FEN rank has fewer than 8 squares");
+            }
+        }
+        if board.white_king.count_ones() != 1 || board.black_king.count_ones() != 1 {
+            return Err("FEN must have exactly one king per side");
+        }
+
+        board.side_to_move = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err("Invalid side to move in FEN"),
+        };
+
+        if fields[2] != "-" {
+            for c in fields[2].chars() {
+                let flag = match c {
+                    'K' => WHITE_KINGSIDE,
+                    'Q' => WHITE_QUEENSIDE,
+                    'k' => BLACK_KINGSIDE,
+                    'q' => BLACK_QUEENSIDE,
+                    _ => return Err("Invalid castling rights in FEN"),
+                };
+                if board.castling_rights & flag != 0 {
+                    return Err("Repeated castling right in FEN");
+                }
+                board.castling_rights |= flag;
+            }
+        }
+
+        if fields[3] != "-" {
+            let square = Square::try_from(fields[3]).map_err(|_| "Invalid en passant square in FEN")?;
+            let expected_rank = if board.side_to_move == Color::White { 5 } else { 2 };
+            if square.rank() != expected_rank {
+                return Err("En passant square in FEN is on the wrong rank");
+            }
+            board.en_passant = Some(square);
+        }
+
+        if let Some(halfmove) = fields.get(4) {
+            board.halfmove_clock = halfmove.parse().map_err(|_| "Invalid halfmove clock in FEN")?;
+        }
+        if let Some(fullmove) = fields.get(5) {
+            board.fullmove_number = match fullmove.parse() {
+                Ok(n) if n >= 1 => n,
+                _ => return Err("Invalid fullmove number in FEN"),
+            };
+        }
+
+        board.update_composite_bitboards();
+        board.hash = board.compute_hash();
+        Ok(board)
+    }
+
+    /// Serializes the board to Forsyth–Edwards Notation (FEN)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chesslib::board::{get_starting_board, STARTING_FEN};
+    /// let board = get_starting_board();
+    ///
+    /// assert_eq!(board.to_fen(), STARTING_FEN);
+    /// ```
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in (0..8).rev() {
+            let mut empty_squares = 0;
+            for file in 0..8 {
+                match self.get_piece_at_square(rank * 8 + file) {
+                    Some(piece) => {
+                        if empty_squares > 0 {
+                            fen.push_str(&empty_squares.to_string());
+                            empty_squares = 0;
+                        }
+                        fen.push(piece.to_fen_char());
+                    }
+                    None => empty_squares += 1,
+                }
+            }
+            if empty_squares > 0 {
+                fen.push_str(&empty_squares.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(match self.side_to_move {
+            Color::White => " w ",
+            Color::Black => " b ",
+        });
+
+        if self.castling_rights == 0 {
+            fen.push('-');
+        } else {
+            for (flag, c) in [(WHITE_KINGSIDE, 'K'), (WHITE_QUEENSIDE, 'Q'), (BLACK_KINGSIDE, 'k'), (BLACK_QUEENSIDE, 'q')] {
+                if self.castling_rights & flag != 0 {
+                    fen.push(c);
+                }
+            }
+        }
+
+        match self.en_passant {
+            Some(square) => fen.push_str(&format!(" {}", square)),
+            None => fen.push_str(" -"),
+        }
+
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+        fen
+    }
+
     /// Returns the Unicode character representation of the chess piece at the given coordinate
     ///
     /// # Arguments
@@ -149,7 +596,7 @@ impl Board {
         }
     }
 
-    fn get_piece_at_square(&self, square_index: u8) -> Option<Piece> {
+    pub(crate) fn get_piece_at_square(&self, square_index: u8) -> Option<Piece> {
         if is_bit_set(self.white_pawns, square_index) {
             Some(Piece::WhitePawn)
         } else if is_bit_set(self.black_pawns, square_index) {
@@ -195,48 +642,299 @@ impl Board {
                     piece.color(), self.side_to_move);
             }
 
+            let previous_hash = self.hash;
+
+            // Take the old castling rights and en passant file out of the key; the new ones
+            // are added back once the move is done
+            self.hash ^= zobrist::castling_key(self.castling_rights);
+            if let Some(square) = self.en_passant {
+                self.hash ^= zobrist::en_passant_key(square.file());
+            }
+
+            let is_pawn = piece == Piece::WhitePawn || piece == Piece::BlackPawn;
+
+            // Captures and pawn moves cannot be undone, so they restart the fifty-move count
+            self.halfmove_clock += 1;
+            if is_pawn {
+                self.halfmove_clock = 0;
+            }
+            if self.side_to_move == Color::Black {
+                self.fullmove_number += 1;
+            }
+
             // First, if there's a piece on the target square, remove it from its bitboard
             if let Some(captured_piece) = self.get_piece_at_square(target_idx) {
-                let captured_bitboard = match captured_piece {
-                    Piece::WhitePawn => &mut self.white_pawns,
-                    Piece::BlackPawn => &mut self.black_pawns,
-                    Piece::WhiteRook => &mut self.white_rooks,
-                    Piece::WhiteKnight => &mut self.white_knights,
-                    Piece::WhiteBishop => &mut self.white_bishops,
-                    Piece::WhiteQueen => &mut self.white_queen,
-                    Piece::WhiteKing => &mut self.white_king,
-                    Piece::BlackRook => &mut self.black_rooks,
-                    Piece::BlackKnight => &mut self.black_knights,
-                    Piece::BlackBishop => &mut self.black_bishops,
-                    Piece::BlackQueen => &mut self.black_queen,
-                    Piece::BlackKing => &mut self.black_king,
-                };
-                *captured_bitboard &= !to_bit;  // Clear the captured piece's bit
+                *self.piece_bitboard_mut(&captured_piece) &= !to_bit;  // Clear the captured piece's bit
+                self.hash ^= zobrist::piece_key(captured_piece, target_idx);
+                self.halfmove_clock = 0;
             }
 
-            // Then move the piece from source to target
-            let piece_bitboard = match piece {
-                Piece::WhitePawn => &mut self.white_pawns,
-                Piece::BlackPawn => &mut self.black_pawns,
-                Piece::WhiteRook => &mut self.white_rooks,
-                Piece::WhiteKnight => &mut self.white_knights,
-                Piece::WhiteBishop => &mut self.white_bishops,
-                Piece::WhiteQueen => &mut self.white_queen,
-                Piece::WhiteKing => &mut self.white_king,
-                Piece::BlackRook => &mut self.black_rooks,
-                Piece::BlackKnight => &mut self.black_knights,
-                Piece::BlackBishop => &mut self.black_bishops,
-                Piece::BlackQueen => &mut self.black_queen,
-                Piece::BlackKing => &mut self.black_king,
+            // A pawn moving onto the en passant square captures the pawn that just passed it,
+            // which sits on the source rank in the target file
+            if is_pawn && Some(mv.target) == self.en_passant {
+                let captured_idx = mv.src.rank() * 8 + mv.target.file();
+                let captured_pawn = if piece == Piece::WhitePawn { Piece::BlackPawn } else { Piece::WhitePawn };
+                *self.piece_bitboard_mut(&captured_pawn) &= !(1u64 << captured_idx);
+                self.hash ^= zobrist::piece_key(captured_pawn, captured_idx);
+            }
+
+            // A double push leaves the skipped square open to en passant for one move
+            self.en_passant = if is_pawn && src_idx.abs_diff(target_idx) == 16 {
+                Some(Square::from_bit_index((src_idx + target_idx) / 2))
+            } else {
+                None
             };
+
+            // A king moving two squares is castling, so the rook has to jump over it
+            if piece == Piece::WhiteKing || piece == Piece::BlackKing {
+                if let Some(castling) = CASTLINGS.iter()
+                    .find(|c| c.king_src == mv.src && c.king_target == mv.target) {
+                    let rook = if piece == Piece::WhiteKing { Piece::WhiteRook } else { Piece::BlackRook };
+                    let rook_bitboard = self.piece_bitboard_mut(&rook);
+                    *rook_bitboard &= !castling.rook_src.to_bitboard();
+                    *rook_bitboard |= castling.rook_target.to_bitboard();
+                    self.hash ^= zobrist::piece_key(rook, castling.rook_src.to_bit_index())
+                        ^ zobrist::piece_key(rook, castling.rook_target.to_bit_index());
+                }
+            }
+
+            // Then move the piece from source to target
+            let piece_bitboard = self.piece_bitboard_mut(&piece);
             *piece_bitboard ^= from_bit;  // Clear the source square
             *piece_bitboard |= to_bit;    // Set the target square
+            self.hash ^= zobrist::piece_key(piece, src_idx) ^ zobrist::piece_key(piece, target_idx);
+
+            // A pawn reaching the last rank is replaced by the promoted piece, a queen unless
+            // the move says otherwise
+            if is_pawn && (mv.target.rank() == 0 || mv.target.rank() == 7) {
+                let promoted = Piece::new(mv.promotion.unwrap_or(PieceType::Queen), piece.color());
+                *self.piece_bitboard_mut(&piece) &= !to_bit;
+                *self.piece_bitboard_mut(&promoted) |= to_bit;
+                self.hash ^= zobrist::piece_key(piece, target_idx) ^ zobrist::piece_key(promoted, target_idx);
+            }
+
+            self.castling_rights &= !(castling_rights_lost(mv.src) | castling_rights_lost(mv.target));
+            self.hash ^= zobrist::castling_key(self.castling_rights);
+            if let Some(square) = self.en_passant {
+                self.hash ^= zobrist::en_passant_key(square.file());
+            }
 
             self.update_composite_bitboards();
-            self.side_to_move = match self.side_to_move {
-                Color::White => Color::Black,
-                Color::Black => Color::White,
+            self.side_to_move = self.side_to_move.opposite();
+            self.hash ^= zobrist::side_key();
+
+            // No position from before a capture or pawn move can come back
+            if self.halfmove_clock == 0 {
+                self.history.clear();
+            } else {
+                self.history.push(previous_hash);
+            }
+
+            debug_assert_eq!(self.hash, self.compute_hash(), "incremental Zobrist key out of sync");
+        }
+    }
+
+    /// Plays the move like `apply_move`, returning the record needed to take it back
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no piece on the move's source square, or it belongs to the side
+    /// that is not to move.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chesslib::board::{get_starting_board, Move};
+    /// use chesslib::Square;
+    /// let mut board = get_starting_board();
+    ///
+    /// let undo = board.make_move(&Move::new(Square::E2, Square::E4));
+    /// board.unmake_move(undo);
+    /// assert_eq!(board, get_starting_board());
+    /// ```
+    pub fn make_move(&mut self, mv: &Move) -> Undo {
+        let moved = self.get_piece_at_square(mv.src.to_bit_index())
+            .expect("Attempted to move from an empty square");
+        let captured = if self.is_en_passant(mv) {
+            Some(Piece::new(PieceType::Pawn, moved.color().opposite()))
+        } else {
+            self.get_piece_at_square(mv.target.to_bit_index())
+        };
+        // The history a capture or pawn move clears is handed to the undo record rather than
+        // copied
+        let history = if captured.is_some() || moved.piece_type() == PieceType::Pawn {
+            std::mem::take(&mut self.history)
+        } else {
+            Vec::new()
+        };
+        let undo = Undo {
+            mv: *mv,
+            moved,
+            captured,
+            castling_rights: self.castling_rights,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: self.hash,
+            history,
+        };
+        self.apply_move(mv);
+        undo
+    }
+
+    /// Takes back the move `undo` was returned for, restoring the exact prior position
+    ///
+    /// Moves have to be taken back in the reverse of the order they were made.
+    pub fn unmake_move(&mut self, undo: Undo) {
+        let mv = &undo.mv;
+        let from_bit = mv.src.to_bitboard();
+        let to_bit = mv.target.to_bitboard();
+        let target_idx = mv.target.to_bit_index();
+
+        // Whatever stands on the target is the mover, or what it promoted to
+        let on_target = self.get_piece_at_square(target_idx)
+            .expect("Attempted to unmake a move whose target square is empty");
+        *self.piece_bitboard_mut(&on_target) &= !to_bit;
+        *self.piece_bitboard_mut(&undo.moved) |= from_bit;
+
+        // Put a castling rook back in its corner
+        if undo.moved == Piece::WhiteKing || undo.moved == Piece::BlackKing {
+            if let Some(castling) = CASTLINGS.iter()
+                .find(|c| c.king_src == mv.src && c.king_target == mv.target) {
+                let rook = if undo.moved == Piece::WhiteKing { Piece::WhiteRook } else { Piece::BlackRook };
+                let rook_bitboard = self.piece_bitboard_mut(&rook);
+                *rook_bitboard &= !castling.rook_target.to_bitboard();
+                *rook_bitboard |= castling.rook_src.to_bitboard();
+            }
+        }
+
+        // A pawn taken en passant stood beside the capturer, not on the target square
+        if let Some(captured) = undo.captured {
+            let is_pawn = undo.moved == Piece::WhitePawn || undo.moved == Piece::BlackPawn;
+            let captured_idx = if is_pawn && Some(mv.target) == undo.en_passant {
+                mv.src.rank() * 8 + mv.target.file()
+            } else {
+                target_idx
             };
+            *self.piece_bitboard_mut(&captured) |= 1u64 << captured_idx;
+        }
+
+        self.castling_rights = undo.castling_rights;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.hash = undo.hash;
+        if undo.captured.is_some() || undo.moved.piece_type() == PieceType::Pawn {
+            self.history = undo.history;
+        } else {
+            self.history.pop();
+        }
+        self.side_to_move = self.side_to_move.opposite();
+        self.update_composite_bitboards();
+
+        debug_assert_eq!(self.hash, self.compute_hash(), "Zobrist key out of sync after unmake");
+    }
+
+    /// Returns true if the move takes an enemy piece, including en passant captures
+    pub fn is_capture(&self, mv: &Move) -> bool {
+        let enemy = match self.side_to_move {
+            Color::White => self.any_black,
+            Color::Black => self.any_white,
+        };
+        enemy & mv.target.to_bitboard() != 0 || self.is_en_passant(mv)
+    }
+
+    /// Returns true if the move is a pawn capturing en passant
+    pub fn is_en_passant(&self, mv: &Move) -> bool {
+        Some(mv.target) == self.en_passant
+            && (self.white_pawns | self.black_pawns) & mv.src.to_bitboard() != 0
+            && mv.src.file() != mv.target.file()
+    }
+
+    /// Returns true if the move is a king castling
+    pub fn is_castling(&self, mv: &Move) -> bool {
+        (self.white_king | self.black_king) & mv.src.to_bitboard() != 0
+            && CASTLINGS.iter().any(|c| c.king_src == mv.src && c.king_target == mv.target)
+    }
+
+    /// Returns true if any piece of the given color attacks the square
+    pub fn is_square_attacked(&self, square: Square, by: Color) -> bool {
+        self.attackers_to(square.to_bit_index(), by, !self.empty) != 0
+    }
+
+    /// Bitboard of the pieces of the given color attacking a square, with sliding pieces
+    /// blocked by `occupied` rather than the actual board occupancy
+    fn attackers_to(&self, square_index: u8, by: Color, occupied: u64) -> u64 {
+        use crate::move_generation::{w_pawn_attacks, b_pawn_attacks, knight_moves,
+                                   king_moves, bishop_attacks, rook_attacks};
+
+        let target = 1u64 << square_index;
+        // Attacks are symmetric, so look outwards from the square for each kind of attacker
+        let (pawns, knights, bishops, rooks, queens, king, pawn_attackers) = match by {
+            Color::White => (self.white_pawns, self.white_knights, self.white_bishops,
+                             self.white_rooks, self.white_queen, self.white_king, b_pawn_attacks(target)),
+            Color::Black => (self.black_pawns, self.black_knights, self.black_bishops,
+                             self.black_rooks, self.black_queen, self.black_king, w_pawn_attacks(target)),
+        };
+
+        (pawn_attackers & pawns)
+            | (knight_moves(target) & knights)
+            | (king_moves(target) & king)
+            | (bishop_attacks(square_index, occupied) & (bishops | queens))
+            | (rook_attacks(square_index, occupied) & (rooks | queens))
+    }
+
+    /// Castling moves available to the side to move: the right must still be held, the
+    /// squares between king and rook empty, and the king may not castle out of, through or into check
+    fn castling_moves(&self) -> Vec<Move> {
+        let (king, rooks) = match self.side_to_move {
+            Color::White => (self.white_king, self.white_rooks),
+            Color::Black => (self.black_king, self.black_rooks),
+        };
+        let enemy = self.side_to_move.opposite();
+
+        CASTLINGS.iter()
+            .filter(|c| self.castling_rights & c.right != 0)
+            .filter(|c| king & c.king_src.to_bitboard() != 0 && rooks & c.rook_src.to_bitboard() != 0)
+            .filter(|c| self.empty & c.must_be_empty == c.must_be_empty)
+            .filter(|c| c.must_be_safe.iter().all(|&square| !self.is_square_attacked(square, enemy)))
+            .map(|c| Move::new(c.king_src, c.king_target))
+            .collect()
+    }
+
+    /// Returns the bitboard holding the given piece
+    pub(crate) fn piece_bitboard(&self, piece: &Piece) -> u64 {
+        match piece {
+            Piece::WhitePawn => self.white_pawns,
+            Piece::BlackPawn => self.black_pawns,
+            Piece::WhiteRook => self.white_rooks,
+            Piece::WhiteKnight => self.white_knights,
+            Piece::WhiteBishop => self.white_bishops,
+            Piece::WhiteQueen => self.white_queen,
+            Piece::WhiteKing => self.white_king,
+            Piece::BlackRook => self.black_rooks,
+            Piece::BlackKnight => self.black_knights,
+            Piece::BlackBishop => self.black_bishops,
+            Piece::BlackQueen => self.black_queen,
+            Piece::BlackKing => self.black_king,
+        }
+    }
+
+    /// Returns the mutable bitboard holding the given piece
+    fn piece_bitboard_mut(&mut self, piece: &Piece) -> &mut u64 {
+        match piece {
+            Piece::WhitePawn => &mut self.white_pawns,
+            Piece::BlackPawn => &mut self.black_pawns,
+            Piece::WhiteRook => &mut self.white_rooks,
+            Piece::WhiteKnight => &mut self.white_knights,
+            Piece::WhiteBishop => &mut self.white_bishops,
+            Piece::WhiteQueen => &mut self.white_queen,
+            Piece::WhiteKing => &mut self.white_king,
+            Piece::BlackRook => &mut self.black_rooks,
+            Piece::BlackKnight => &mut self.black_knights,
+            Piece::BlackBishop => &mut self.black_bishops,
+            Piece::BlackQueen => &mut self.black_queen,
+            Piece::BlackKing => &mut self.black_king,
         }
     }
 
@@ -253,10 +951,15 @@ impl Board {
         self.empty = !(self.any_white | self.any_black);
     }
 
-    pub fn apply_move_from_string(&mut self, mv_str: &str) {
-        if let Ok(mv) = Move::try_from(mv_str) {
-            self.apply_move(&mv);
+    /// Plays a move given in coordinate notation, such as `e2e4` or `e7e8q`, if it is legal
+    /// in the position; otherwise the board is left as it was
+    pub fn apply_move_from_string(&mut self, mv_str: &str) -> Result<(), &'static str> {
+        let mv = Move::try_from(mv_str)?;
+        if !self.legal_moves().contains(&mv) {
+            return Err("Illegal move");
         }
+        self.apply_move(&mv);
+        Ok(())
     }
 
     pub fn apply_moves(&mut self, moves: impl Iterator<Item = Move>) {
@@ -265,144 +968,238 @@ impl Board {
         }
     }
 
-    pub fn apply_moves_from_strings(&mut self, moves: impl Iterator<Item = String>) {
+    /// Plays moves in coordinate notation one after the other, stopping at the first that
+    /// cannot be played
+    pub fn apply_moves_from_strings(&mut self, moves: impl Iterator<Item = String>) -> Result<(), &'static str> {
         for mv in moves {
-            self.apply_move_from_string(&mv);
+            self.apply_move_from_string(&mv)?;
         }
+        Ok(())
     }
 
     pub fn convert_moves(moves: impl Iterator<Item = String>) -> impl Iterator<Item = Result<Move, &'static str>> {
         moves.map(|mv| Move::try_from(mv.as_str()))
     }
 
-    pub fn get_next_moves(&self, n: i32) -> Vec<String> {
-        use crate::move_generation::{w_pawns_able_to_push, b_pawns_able_to_push,
-                                   w_pawns_able_to_double_push, b_pawns_able_to_double_push,
-                                   w_pawns_attack_targets, b_pawns_attack_targets,
-                                   knight_legal_moves, bishop_legal_moves, rook_legal_moves,
-                                   queen_legal_moves, king_legal_moves};
-        use rand::seq::IteratorRandom;
-
-        let mut possible_moves = Vec::new();
-
-        if self.side_to_move == Color::Black {
-            // Get all possible pawn moves
-            let moveable_pawns = b_pawns_able_to_push(self.black_pawns, self.empty);
-            let double_moveable_pawns = b_pawns_able_to_double_push(self.black_pawns, self.empty);
-            let attacking_pawns = b_pawns_attack_targets(self.black_pawns, self.any_white);
-
-            possible_moves.extend(bitboard_to_pawn_single_moves(moveable_pawns, true));
-            possible_moves.extend(bitboard_to_pawn_double_moves(double_moveable_pawns, true));
-            possible_moves.extend(bitboard_to_pawn_capture_moves(self.black_pawns, attacking_pawns, true));
+    /// Returns every legal move for the side to move
+    ///
+    /// Unlike plain piece movement this never leaves the mover's king in check: pinned pieces
+    /// only move along the pin, the king never steps onto an attacked square, and when in check
+    /// only moves that capture the checker, block it or move the king away are generated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chesslib::board::{Board, get_starting_board};
+    /// assert_eq!(get_starting_board().legal_moves().len(), 20);
+    ///
+    /// // The e2 bishop is pinned against the king by the rook on e8
+    /// let board = Board::from_fen("4r1k1/8/8/8/8/8/4B3/4K3 w - - 0 1").unwrap();
+    /// assert!(board.legal_moves().iter().all(|mv| mv.src.to_string() != "e2"));
+    /// ```
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.generate_legal_moves(false)
+    }
 
-            // Process each black knight separately
-            let mut working_knights = self.black_knights;
-            while working_knights != 0 {
-                let knight_pos = working_knights.trailing_zeros() as u8;
-                working_knights &= working_knights - 1;  // Clear the processed bit
+    /// Returns the legal captures, en passant included, and promotions for the side to move
+    ///
+    /// These are the moves that change the material balance, which is all a quiescence
+    /// search needs to look at.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chesslib::board::{Board, get_starting_board};
+    /// assert!(get_starting_board().legal_captures().is_empty());
+    ///
+    /// let board = Board::from_fen("4k3/P7/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+    /// let captures: Vec<String> = board.legal_captures().iter().map(|mv| mv.to_string()).collect();
+    /// assert_eq!(captures, ["e4d5", "a7a8q", "a7a8r", "a7a8b", "a7a8n"]);
+    /// ```
+    pub fn legal_captures(&self) -> Vec<Move> {
+        self.generate_legal_moves(true)
+    }
 
-                let single_knight = 1u64 << knight_pos;
-                // Get all legal moves for this knight (including both empty squares and captures)
-                let moves = knight_legal_moves(single_knight, self.any_black);
-                possible_moves.extend(self.bitboard_to_moves(single_knight, moves));
-            }
+    fn generate_legal_moves(&self, captures_only: bool) -> Vec<Move> {
+        use crate::move_generation::{knight_legal_moves, bishop_legal_moves, rook_legal_moves,
+                                   queen_legal_moves, king_legal_moves, bishop_attacks, rook_attacks,
+                                   knight_attack_targets, bishop_attack_targets, rook_attack_targets,
+                                   queen_attack_targets, king_attack_targets,
+                                   w_pawns_attack_targets, b_pawns_attack_targets,
+                                   w_pawn_attacks, b_pawn_attacks, w_pawns_able_to_push, b_pawns_able_to_push,
+                                   w_pawns_able_to_double_push, b_pawns_able_to_double_push,
+                                   squares_between, line_through};
 
-            // Process each black bishop separately
-            let mut working_bishops = self.black_bishops;
-            while working_bishops != 0 {
-                let bishop_pos = working_bishops.trailing_zeros() as u8;
-                working_bishops &= working_bishops - 1;
+        let mut moves = Vec::new();
+        let us = self.side_to_move;
+        let them = us.opposite();
+        let (own, enemy) = match us {
+            Color::White => (self.any_white, self.any_black),
+            Color::Black => (self.any_black, self.any_white),
+        };
+        let (pawns, knights, bishops, rooks, queens, king) = match us {
+            Color::White => (self.white_pawns, self.white_knights, self.white_bishops,
+                             self.white_rooks, self.white_queen, self.white_king),
+            Color::Black => (self.black_pawns, self.black_knights, self.black_bishops,
+                             self.black_rooks, self.black_queen, self.black_king),
+        };
+        let (enemy_diagonal, enemy_orthogonal) = match us {
+            Color::White => (self.black_bishops | self.black_queen, self.black_rooks | self.black_queen),
+            Color::Black => (self.white_bishops | self.white_queen, self.white_rooks | self.white_queen),
+        };
+        let occupied = own | enemy;
+        let king_idx = king.trailing_zeros() as u8;
 
-                let single_bishop = 1u64 << bishop_pos;
-                let moves = bishop_legal_moves(single_bishop, self.any_black, self.any_white);
-                possible_moves.extend(self.bitboard_to_moves(single_bishop, moves));
+        // The king may go anywhere not attacked once it has left its square, so sliders
+        // checking it along a line also cover the square behind it
+        let mut king_targets = if captures_only {
+            king_attack_targets(king, enemy)
+        } else {
+            king_legal_moves(king, own)
+        };
+        while king_targets != 0 {
+            let to_idx = king_targets.trailing_zeros() as u8;
+            king_targets &= king_targets - 1;
+            if self.attackers_to(to_idx, them, occupied ^ king) == 0 {
+                moves.push(Move::new(Square::from_bit_index(king_idx), Square::from_bit_index(to_idx)));
             }
+        }
 
-            // Process each black rook separately
-            let mut working_rooks = self.black_rooks;
-            while working_rooks != 0 {
-                let rook_pos = working_rooks.trailing_zeros() as u8;
-                working_rooks &= working_rooks - 1;
-
-                let single_rook = 1u64 << rook_pos;
-                let moves = rook_legal_moves(single_rook, self.any_black, self.any_white);
-                possible_moves.extend(self.bitboard_to_moves(single_rook, moves));
-            }
+        let checkers = self.attackers_to(king_idx, them, occupied);
+        if checkers.count_ones() > 1 {
+            // Double check can only be answered by moving the king
+            return moves;
+        }
 
-            // Process each black queen separately (usually just one)
-            let mut working_queens = self.black_queen;
-            while working_queens != 0 {
-                let queen_pos = working_queens.trailing_zeros() as u8;
-                working_queens &= working_queens - 1;
+        // Squares other pieces may move to: anywhere when not in check, otherwise only
+        // onto the checking piece or in between it and the king
+        let evasion_mask = if checkers != 0 {
+            let checker_idx = checkers.trailing_zeros() as u8;
+            checkers | squares_between(king_idx, checker_idx)
+        } else {
+            !0u64
+        };
 
-                let single_queen = 1u64 << queen_pos;
-                let moves = queen_legal_moves(single_queen, self.any_black, self.any_white);
-                possible_moves.extend(self.bitboard_to_moves(single_queen, moves));
+        // A piece is pinned when it is the only piece between the king and an enemy slider
+        let mut pinned = 0u64;
+        let mut snipers = (bishop_attacks(king_idx, enemy) & enemy_diagonal)
+            | (rook_attacks(king_idx, enemy) & enemy_orthogonal);
+        while snipers != 0 {
+            let sniper_idx = snipers.trailing_zeros() as u8;
+            snipers &= snipers - 1;
+            let blockers = squares_between(king_idx, sniper_idx) & occupied;
+            if blockers.count_ones() == 1 && blockers & own != 0 {
+                pinned |= blockers;
             }
+        }
+        // Pinned pieces may still move along the line between king and pinner
+        let pin_mask = |from_idx: u8| -> u64 {
+            if pinned & (1u64 << from_idx) != 0 { line_through(king_idx, from_idx) } else { !0u64 }
+        };
 
-            // Process black king (only one)
-            let moves = king_legal_moves(self.black_king, self.any_black);
-            possible_moves.extend(self.bitboard_to_moves(self.black_king, moves));
-
+        let piece_moves: [(u64, &dyn Fn(u64) -> u64); 4] = if captures_only {
+            [
+                (knights, &|piece| knight_attack_targets(piece, enemy)),
+                (bishops, &|piece| bishop_attack_targets(piece, own, enemy)),
+                (rooks, &|piece| rook_attack_targets(piece, own, enemy)),
+                (queens, &|piece| queen_attack_targets(piece, own, enemy)),
+            ]
         } else {
-            // Get all possible pawn moves
-            let moveable_pawns = w_pawns_able_to_push(self.white_pawns, self.empty);
-            let double_moveable_pawns = w_pawns_able_to_double_push(self.white_pawns, self.empty);
-            let attacking_pawns = w_pawns_attack_targets(self.white_pawns, self.any_black);
-
-            possible_moves.extend(bitboard_to_pawn_single_moves(moveable_pawns, false));
-            possible_moves.extend(bitboard_to_pawn_double_moves(double_moveable_pawns, false));
-            possible_moves.extend(bitboard_to_pawn_capture_moves(self.white_pawns, attacking_pawns, false));
-
-            // Process each white knight separately
-            let mut working_knights = self.white_knights;
-            while working_knights != 0 {
-                let knight_pos = working_knights.trailing_zeros() as u8;
-                working_knights &= working_knights - 1;  // Clear the bit we are processing, the lowest significant bit that is set
-
-                let single_knight = 1u64 << knight_pos;
-                // Get all legal moves for this knight (including both empty squares and captures)
-                let moves = knight_legal_moves(single_knight, self.any_white);
-                possible_moves.extend(self.bitboard_to_moves(single_knight, moves));
+            [
+                (knights, &|piece| knight_legal_moves(piece, own)),
+                (bishops, &|piece| bishop_legal_moves(piece, own, enemy)),
+                (rooks, &|piece| rook_legal_moves(piece, own, enemy)),
+                (queens, &|piece| queen_legal_moves(piece, own, enemy)),
+            ]
+        };
+        for (pieces, targets_for) in piece_moves {
+            let mut working_pieces = pieces;
+            while working_pieces != 0 {
+                let from_idx = working_pieces.trailing_zeros() as u8;
+                working_pieces &= working_pieces - 1;
+                let targets = targets_for(1u64 << from_idx) & evasion_mask & pin_mask(from_idx);
+                push_moves(&mut moves, from_idx, targets);
             }
+        }
 
-            // Process each white bishop separately
-            let mut working_bishops = self.white_bishops;
-            while working_bishops != 0 {
-                let bishop_pos = working_bishops.trailing_zeros() as u8;
-                working_bishops &= working_bishops - 1;
-
-                let single_bishop = 1u64 << bishop_pos;
-                let moves = bishop_legal_moves(single_bishop, self.any_white, self.any_black);
-                possible_moves.extend(self.bitboard_to_moves(single_bishop, moves));
+        let mut working_pawns = pawns;
+        while working_pawns != 0 {
+            let from_idx = working_pawns.trailing_zeros() as u8;
+            working_pawns &= working_pawns - 1;
+            let pawn = 1u64 << from_idx;
+
+            // Only pushes onto the last rank change the material when generating captures
+            let push_mask = if captures_only { LAST_RANKS } else { !0u64 };
+            let mut targets = match us {
+                Color::White => {
+                    let mut targets = w_pawns_attack_targets(pawn, enemy);
+                    if w_pawns_able_to_push(pawn, self.empty) != 0 {
+                        targets |= (pawn << 8) & push_mask;
+                    }
+                    if !captures_only && w_pawns_able_to_double_push(pawn, self.empty) != 0 {
+                        targets |= pawn << 16;
+                    }
+                    targets
+                }
+                Color::Black => {
+                    let mut targets = b_pawns_attack_targets(pawn, enemy);
+                    if b_pawns_able_to_push(pawn, self.empty) != 0 {
+                        targets |= (pawn >> 8) & push_mask;
+                    }
+                    if !captures_only && b_pawns_able_to_double_push(pawn, self.empty) != 0 {
+                        targets |= pawn >> 16;
+                    }
+                    targets
+                }
+            };
+            targets &= evasion_mask & pin_mask(from_idx);
+
+            while targets != 0 {
+                let to_idx = targets.trailing_zeros() as u8;
+                targets &= targets - 1;
+                let (src, target) = (Square::from_bit_index(from_idx), Square::from_bit_index(to_idx));
+                if target.rank() == 0 || target.rank() == 7 {
+                    for piece_type in PROMOTION_PIECES {
+                        moves.push(Move { src, target, promotion: Some(piece_type) });
+                    }
+                } else {
+                    moves.push(Move::new(src, target));
+                }
             }
+        }
 
-            // Process each white rook separately
-            let mut working_rooks = self.white_rooks;
-            while working_rooks != 0 {
-                let rook_pos = working_rooks.trailing_zeros() as u8;
-                working_rooks &= working_rooks - 1;
-
-                let single_rook = 1u64 << rook_pos;
-                let moves = rook_legal_moves(single_rook, self.any_white, self.any_black);
-                possible_moves.extend(self.bitboard_to_moves(single_rook, moves));
+        // En passant removes two pieces from the capturing pawn's rank at once, which can
+        // expose the king in ways pin detection misses, so look at the king from the
+        // occupancy the capture leaves behind, without the pawn it takes
+        if let Some(ep_square) = self.en_passant {
+            let mut capturers = match us {
+                Color::White => b_pawn_attacks(ep_square.to_bitboard()) & pawns,
+                Color::Black => w_pawn_attacks(ep_square.to_bitboard()) & pawns,
+            };
+            while capturers != 0 {
+                let from_idx = capturers.trailing_zeros() as u8;
+                capturers &= capturers - 1;
+                let mv = Move::new(Square::from_bit_index(from_idx), ep_square);
+                let captured = 1u64 << (mv.src.rank() * 8 + ep_square.file());
+                let after = (occupied & !(1u64 << from_idx) & !captured) | ep_square.to_bitboard();
+                if self.attackers_to(king_idx, them, after) & !captured == 0 {
+                    moves.push(mv);
+                }
             }
+        }
 
-            // Process each white queen separately (usually just one)
-            let mut working_queens = self.white_queen;
-            while working_queens != 0 {
-                let queen_pos = working_queens.trailing_zeros() as u8;
-                working_queens &= working_queens - 1;
+        if checkers == 0 && !captures_only {
+            moves.extend(self.castling_moves());
+        }
 
-                let single_queen = 1u64 << queen_pos;
-                let moves = queen_legal_moves(single_queen, self.any_white, self.any_black);
-                possible_moves.extend(self.bitboard_to_moves(single_queen, moves));
-            }
+        moves
+    }
 
-            // Process white king (only one)
-            let moves = king_legal_moves(self.white_king, self.any_white);
-            possible_moves.extend(self.bitboard_to_moves(self.white_king, moves));
-        }
+    /// Returns `n` randomly chosen legal moves in UCI notation, or all of them when `n` is -1
+    pub fn get_next_moves(&self, n: i32) -> Vec<String> {
+        use rand::seq::IteratorRandom;
+
+        let possible_moves: Vec<String> = self.legal_moves().iter().map(|mv| mv.to_string()).collect();
 
         if n == -1 {
             possible_moves
@@ -421,49 +1218,119 @@ impl Board {
         }
     }
 
+    /// Returns a random legal move, or the UCI null move `0000` when the game is over
     pub fn get_next_move(&self) -> String {
         // Default to getting one move
         self.get_next_moves(1)
             .into_iter()
             .next()
-            .expect("No moves found, which should be impossible in current state")
+            .unwrap_or_else(|| NULL_MOVE.to_string())
     }
 
-    // Generic helper function to convert a source bitboard and target bitboard into a list of moves
-    fn bitboard_to_moves(&self, source_pieces: u64, target_squares: u64) -> Vec<String> {
-        // Assert that source_pieces contains exactly one piece (one bit set)
-        debug_assert_eq!(source_pieces.count_ones(), 1,
-            "bitboard_to_moves should be called with exactly one source piece, got {} pieces",
-            source_pieces.count_ones());
+    /// Returns true if the side to move is in check
+    pub fn is_check(&self) -> bool {
+        let king = match self.side_to_move {
+            Color::White => self.white_king,
+            Color::Black => self.black_king,
+        };
+        self.attackers_to(king.trailing_zeros() as u8, self.side_to_move.opposite(), !self.empty) != 0
+    }
 
-        let mut moves = Vec::new();
-        let mut working_source = source_pieces;
-
-        // For each source piece
-        while working_source != 0 {
-            let from_square = working_source.trailing_zeros() as u8;
-            working_source &= working_source - 1;  // Clear the processed bit
-
-            // For each target square
-            let mut current_targets = target_squares;
-            while current_targets != 0 {
-                let to_square = current_targets.trailing_zeros() as u8;
-                current_targets &= current_targets - 1;  // Clear the processed bit
-
-                // Convert to algebraic notation
-                let from_file = int_file_to_string(from_square % 8);
-                let from_rank = (from_square / 8 + 1).to_string();
-                let to_file = int_file_to_string(to_square % 8);
-                let to_rank = (to_square / 8 + 1).to_string();
-
-                moves.push(format!("{}{}{}{}", from_file, from_rank, to_file, to_rank));
+    /// Returns true if the side to move is in check and has no legal move
+    pub fn is_checkmate(&self) -> bool {
+        self.is_check() && self.legal_moves().is_empty()
+    }
+
+    /// Returns true if the side to move is not in check but has no legal move
+    pub fn is_stalemate(&self) -> bool {
+        !self.is_check() && self.legal_moves().is_empty()
+    }
+
+    /// Halfmoves since the last capture or pawn move, as counted for the fifty-move rule
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    /// Number of the current full move, starting at 1 and incremented after Black moves
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    /// Returns true if fifty moves by each side have passed without a capture or a pawn move
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= FIFTY_MOVE_LIMIT
+    }
+
+    /// Returns true if the position has now occurred at least `count` times with the same
+    /// side to move and the same castling and en passant rights
+    ///
+    /// Only positions since the last capture or pawn move are compared, as none before it can
+    /// come back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chesslib::board::get_starting_board;
+    /// let mut board = get_starting_board();
+    ///
+    /// board.apply_moves_from_strings(["g1f3", "g8f6", "f3g1", "f6g8"].iter().map(|s| s.to_string())).unwrap();
+    /// assert!(board.is_repetition(2));
+    /// assert!(!board.is_repetition(3));
+    /// ```
+    pub fn is_repetition(&self, count: usize) -> bool {
+        // The last entry is the position before the last move, with the other side to move
+        let earlier = self.history.iter().rev()
+            .skip(1)
+            .step_by(2)
+            .filter(|&&key| key == self.hash)
+            .count();
+        earlier + 1 >= count
+    }
+
+    /// Returns how the game has ended, or `None` while it goes on
+    ///
+    /// A checkmate delivered on the move that reaches the fifty-move limit still wins.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chesslib::board::{get_starting_board, Color, GameResult};
+    /// let mut board = get_starting_board();
+    /// assert_eq!(board.game_result(), None);
+    ///
+    /// board.apply_moves_from_strings(["f2f3", "e7e5", "g2g4", "d8h4"].iter().map(|s| s.to_string())).unwrap();
+    /// assert_eq!(board.game_result(), Some(GameResult::Checkmate { winner: Color::Black }));
+    /// ```
+    pub fn game_result(&self) -> Option<GameResult> {
+        if !self.legal_moves().is_empty() {
+            if self.is_fifty_move_draw() {
+                Some(GameResult::FiftyMoveRule)
+            } else if self.is_repetition(3) {
+                Some(GameResult::ThreefoldRepetition)
+            } else {
+                None
             }
+        } else if self.is_check() {
+            Some(GameResult::Checkmate { winner: self.side_to_move.opposite() })
+        } else {
+            Some(GameResult::Stalemate)
         }
+    }
+}
 
-        moves
+/// Displays the board as its FEN, so `board.to_string().parse::<Board>()` round trips
+impl std::fmt::Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_fen())
     }
 }
 
+impl std::str::FromStr for Board {
+    type Err = &'static str;
+    fn from_str(fen: &str) -> Result<Self, Self::Err> {
+        Board::from_fen(fen)
+    }
+}
 
 pub fn get_starting_board() -> Board {
     let white_pawns = (1 << (8 + 0)) + (1 << (8 + 1)) + (1 << (8 + 2)) + (1 << (8 + 3)) +
@@ -498,12 +1365,29 @@ pub fn get_starting_board() -> Board {
         any_white: 0,
         any_black: 0,
         empty: 0,
-        side_to_move: Color::White
+        side_to_move: Color::White,
+        castling_rights: WHITE_KINGSIDE | WHITE_QUEENSIDE | BLACK_KINGSIDE | BLACK_QUEENSIDE,
+        en_passant: None,
+        halfmove_clock: 0,
+        fullmove_number: 1,
+        hash: 0,
+        history: Vec::new(),
     };
     board.update_composite_bitboards();
+    board.hash = board.compute_hash();
     board
 }
 
+/// Adds a move from one square to each of the target squares
+fn push_moves(moves: &mut Vec<Move>, from_idx: u8, mut targets: u64) {
+    let src = Square::from_bit_index(from_idx);
+    while targets != 0 {
+        let to_idx = targets.trailing_zeros() as u8;
+        targets &= targets - 1;
+        moves.push(Move::new(src, Square::from_bit_index(to_idx)));
+    }
+}
+
 pub fn int_file_to_string(file: u8) -> &'static str {
     match file {
         0 => A,
@@ -552,13 +1436,24 @@ pub fn bitboard_to_pawn_single_moves(bitboard: u64, is_black: bool) -> Vec<Strin
                 };
                 let from = format!("{}{}", int_file_to_string(file), rank + 1);
                 let to = format!("{}{}", int_file_to_string(file), to_rank + 1);
-                moves.push(format!("{}{}", from, to));
+                push_pawn_move(&mut moves, format!("{}{}", from, to), to_rank);
             }
         }
     }
     moves
 }
 
+/// Adds a pawn move, expanded into one move per promotion piece when it reaches the last rank
+fn push_pawn_move(moves: &mut Vec<String>, mv: String, to_rank: u8) {
+    if to_rank == 0 || to_rank == 7 {
+        for piece_type in PROMOTION_PIECES {
+            moves.push(format!("{}{}", mv, piece_type.to_promotion_char()));
+        }
+    } else {
+        moves.push(mv);
+    }
+}
+
 pub fn bitboard_to_pawn_double_moves(bitboard: u64, is_black: bool) -> Vec<String> {
     let mut moves = Vec::new();
     for rank in 0..8 {
@@ -589,34 +1484,30 @@ pub fn bitboard_to_pawn_capture_moves(from_bitboard: u64, target_bitboard: u64,
         // Clear the processed bit
         working_board &= working_board - 1;
 
-        // Find the source pawn that can attack this square
-        let from_square = if is_black {
-            // Check both possible source squares for black pawns (one rank up, one file left or right)
-            let possible_from_east = to_square + 7;
-            let possible_from_west = to_square + 9;
-            if from_bitboard & (1 << possible_from_east) != 0 {
-                possible_from_east
-            } else {
-                possible_from_west
-            }
+        // Up to two pawns can attack the same square, one from each neighbouring file
+        let (from_west, from_east) = if is_black {
+            // Black pawns capture downwards, so the source is one rank up
+            (to_square + 7, to_square + 9)
         } else {
-            // Check both possible source squares for white pawns (one rank down, one file left or right)
-            let possible_from_east = to_square - 9;
-            let possible_from_west = to_square - 7;
-            if from_bitboard & (1 << possible_from_east) != 0 {
-                possible_from_east
-            } else {
-                possible_from_west
-            }
+            // White pawns capture upwards, so the source is one rank down
+            (to_square.wrapping_sub(9), to_square.wrapping_sub(7))
         };
+        let to_file = to_square % 8;
+        let candidates = [(to_file != 0, from_west), (to_file != 7, from_east)];
+
+        for (on_board, from_square) in candidates {
+            if !on_board || from_bitboard & (1u64 << from_square) == 0 {
+                continue;
+            }
 
-        // Convert to algebraic notation
-        let from_file = int_file_to_string(from_square % 8);
-        let from_rank = (from_square / 8 + 1).to_string();
-        let to_file = int_file_to_string(to_square % 8);
-        let to_rank = (to_square / 8 + 1).to_string();
+            // Convert to algebraic notation
+            let from_file = int_file_to_string(from_square % 8);
+            let from_rank = (from_square / 8 + 1).to_string();
+            let to_file = int_file_to_string(to_square % 8);
+            let to_rank = (to_square / 8 + 1).to_string();
 
-        moves.push(format!("{}{}{}{}", from_file, from_rank, to_file, to_rank));
+            push_pawn_move(&mut moves, format!("{}{}{}{}", from_file, from_rank, to_file, to_rank), to_square / 8);
+        }
     }
     moves
 }
@@ -795,12 +1686,12 @@ mod tests {
         let mut board = get_starting_board();
 
         // Test moving a white pawn from e2 to e4
-        board.apply_move(&Move { src: Square::E2, target: Square::E4 });
+        board.apply_move(&Move::new(Square::E2, Square::E4));
         assert!(is_bit_set(board.white_pawns, Square::E4.to_bit_index()));
         assert!(!is_bit_set(board.white_pawns, Square::E2.to_bit_index()));
 
         // Test moving a black pawn from d7 to d5
-        board.apply_move(&Move { src: Square::D7, target: Square::D5 });
+        board.apply_move(&Move::new(Square::D7, Square::D5));
         assert!(is_bit_set(board.black_pawns, Square::D5.to_bit_index()));
         assert!(!is_bit_set(board.black_pawns, Square::D7.to_bit_index()));
 
@@ -814,15 +1705,15 @@ mod tests {
         assert_eq!(board.side_to_move, Color::White);
 
         // First move: White e2e4
-        board.apply_move(&Move { src: Square::E2, target: Square::E4 });
+        board.apply_move(&Move::new(Square::E2, Square::E4));
         assert_eq!(board.side_to_move, Color::Black);
 
         // Second move: Black d7d6
-        board.apply_move(&Move { src: Square::D7, target: Square::D6 });
+        board.apply_move(&Move::new(Square::D7, Square::D6));
         assert_eq!(board.side_to_move, Color::White);
 
         // Third move: White g2g4
-        board.apply_move(&Move { src: Square::G2, target: Square::G4 });
+        board.apply_move(&Move::new(Square::G2, Square::G4));
         assert_eq!(board.side_to_move, Color::Black);
 
         // Get next move - should suggest a black move
@@ -854,12 +1745,12 @@ mod tests {
         let mut board = get_starting_board();
 
         // Test moving a white pawn from e2 to e4
-        board.apply_move(&Move { src: Square::E2, target: Square::E4 });
+        board.apply_move(&Move::new(Square::E2, Square::E4));
         assert!(is_bit_set(board.white_pawns, Square::E4.to_bit_index()));
         assert!(!is_bit_set(board.white_pawns, Square::E2.to_bit_index()));
 
         // Test moving another white pawn from d2 to d4 - should panic
-        board.apply_move(&Move { src: Square::D2, target: Square::D4 });
+        board.apply_move(&Move::new(Square::D2, Square::D4));
     }
 
     #[test]
@@ -867,80 +1758,24 @@ mod tests {
         let mut board = get_starting_board();
 
         // Move white knight from b1 to c3
-        board.apply_move(&Move { src: Square::B1, target: Square::C3 });
+        board.apply_move(&Move::new(Square::B1, Square::C3));
         assert!(is_bit_set(board.white_knights, Square::C3.to_bit_index()));
         assert!(!is_bit_set(board.white_knights, Square::B1.to_bit_index()));
         assert_eq!(board.side_to_move, Color::Black);
 
         // Move black knight from g8 to f6
-        board.apply_move(&Move { src: Square::G8, target: Square::F6 });
+        board.apply_move(&Move::new(Square::G8, Square::F6));
         assert!(is_bit_set(board.black_knights, Square::F6.to_bit_index()));
         assert!(!is_bit_set(board.black_knights, Square::G8.to_bit_index()));
         assert_eq!(board.side_to_move, Color::White);
 
         // Test a capture: white knight takes black pawn
-        board.apply_move(&Move { src: Square::C3, target: Square::D5 });
+        board.apply_move(&Move::new(Square::C3, Square::D5));
         assert!(is_bit_set(board.white_knights, Square::D5.to_bit_index()));
         assert!(!is_bit_set(board.white_knights, Square::C3.to_bit_index()));
         assert!(!is_bit_set(board.black_pawns, Square::D5.to_bit_index()));
     }
 
-    #[test]
-    fn test_bitboard_to_moves() {
-        let board = get_starting_board();
-
-        // Test with a single source and multiple targets
-        let source = Square::E4.to_bitboard();  // Knight on e4
-        let targets = Square::F6.to_bitboard() | Square::D6.to_bitboard() | Square::C5.to_bitboard();
-
-        let moves = board.bitboard_to_moves(source, targets);
-
-        // Verify the moves are generated correctly
-        assert!(moves.contains(&"e4f6".to_string()));
-        assert!(moves.contains(&"e4d6".to_string()));
-        assert!(moves.contains(&"e4c5".to_string()));
-        assert_eq!(moves.len(), 3);
-    }
-
-    #[test]
-    fn test_bitboard_to_moves2() {
-        let board = get_starting_board();
-
-        // Test with a different single source and single target
-        let source = Square::G1.to_bitboard();  // Knight on g1
-        let target = Square::F3.to_bitboard();  // Target square f3
-
-        let moves = board.bitboard_to_moves(source, target);
-
-        // Verify move is generated correctly
-        assert!(moves.contains(&"g1f3".to_string()));
-        assert_eq!(moves.len(), 1);
-    }
-
-    #[test]
-    fn test_bitboard_to_moves3() {
-        let board = get_starting_board();
-
-        // Test b1 knight separately
-        let source = Square::B1.to_bitboard();  // Knight on b1
-        let target = Square::C3.to_bitboard();  // Target square c3
-
-        let moves = board.bitboard_to_moves(source, target);
-
-        // Verify move is generated correctly
-        assert!(moves.contains(&"b1c3".to_string()));
-        assert_eq!(moves.len(), 1);
-    }
-
-    #[test]
-    fn test_bitboard_to_moves4() {
-        let board = get_starting_board();
-        let source = Square::E4.to_bitboard();  // Knight on e4
-
-        // Test with no target squares (should produce empty move list)
-        assert!(board.bitboard_to_moves(source, 0).is_empty());
-    }
-
     #[test]
     fn test_get_next_move() {
         let mut board = get_starting_board();
@@ -956,7 +1791,7 @@ mod tests {
                "First move {} should be a white pawn or knight move", first_move);
 
         // Apply the first move and get a response from black
-        board.apply_move_from_string(&first_move);
+        board.apply_move_from_string(&first_move).unwrap();
         assert_eq!(board.side_to_move, Color::Black);
 
         let black_move = board.get_next_move();
@@ -968,7 +1803,7 @@ mod tests {
                "Move {} should be a black pawn or knight move", black_move);
 
         // Apply black's move and get another white move
-        board.apply_move_from_string(&black_move);
+        board.apply_move_from_string(&black_move).unwrap();
         assert_eq!(board.side_to_move, Color::White);
 
         // Get another move - make sure it's still valid format
@@ -1013,4 +1848,520 @@ mod tests {
         // So we expect exactly 20 possible moves
         assert_eq!(all_moves.len(), 20, "Starting position should have exactly 20 possible moves");
     }
+
+    #[test]
+    fn test_from_fen_starting_position() {
+        let board = Board::from_fen(STARTING_FEN).unwrap();
+        let expected = get_starting_board();
+
+        assert_eq!(board.white_pawns, expected.white_pawns);
+        assert_eq!(board.white_knights, expected.white_knights);
+        assert_eq!(board.white_bishops, expected.white_bishops);
+        assert_eq!(board.white_rooks, expected.white_rooks);
+        assert_eq!(board.white_queen, expected.white_queen);
+        assert_eq!(board.white_king, expected.white_king);
+        assert_eq!(board.black_pawns, expected.black_pawns);
+        assert_eq!(board.black_knights, expected.black_knights);
+        assert_eq!(board.black_bishops, expected.black_bishops);
+        assert_eq!(board.black_rooks, expected.black_rooks);
+        assert_eq!(board.black_queen, expected.black_queen);
+        assert_eq!(board.black_king, expected.black_king);
+        assert_eq!(board.empty, expected.empty);
+        assert_eq!(board.side_to_move, Color::White);
+        assert_eq!(board.castling_rights, WHITE_KINGSIDE | WHITE_QUEENSIDE | BLACK_KINGSIDE | BLACK_QUEENSIDE);
+        assert_eq!(board.en_passant, None);
+        assert_eq!(board.halfmove_clock(), 0);
+        assert_eq!(board.fullmove_number(), 1);
+    }
+
+    #[test]
+    fn test_from_fen_game_state_fields() {
+        let board = Board::from_fen("rnbqkbnr/pp1ppppp/8/2pP4/8/8/PPP1PPPP/RNBQKBNR w Kq c6 3 7").unwrap();
+
+        assert_eq!(board.get_piece_at_coordinate("d5"), "♙");
+        assert_eq!(board.get_piece_at_coordinate("c5"), "♟");
+        assert_eq!(board.get_piece_at_coordinate("d2"), " ");
+        assert_eq!(board.side_to_move, Color::White);
+        assert_eq!(board.castling_rights, WHITE_KINGSIDE | BLACK_QUEENSIDE);
+        assert_eq!(board.en_passant, Some(Square::C6));
+        assert_eq!(board.halfmove_clock(), 3);
+        assert_eq!(board.fullmove_number(), 7);
+    }
+
+    #[test]
+    fn test_from_fen_optional_clocks() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+        assert_eq!(board.side_to_move, Color::Black);
+        assert_eq!(board.castling_rights, 0);
+        assert_eq!(board.halfmove_clock(), 0);
+        assert_eq!(board.fullmove_number(), 1);
+    }
+
+    #[test]
+    fn test_from_fen_rejects_malformed_input() {
+        assert!(Board::from_fen("").is_err());
+        assert!(Board::from_fen("8/8/8/8/8/8/8 w - - 0 1").is_err(), "seven ranks");
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K2 w - - 0 1").is_err(), "short rank");
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K4 w - - 0 1").is_err(), "long rank");
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4X3 w - - 0 1").is_err(), "unknown piece");
+        assert!(Board::from_fen("8/8/8/8/8/8/8/4K3 w - - 0 1").is_err(), "missing black king");
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 x - - 0 1").is_err(), "bad side to move");
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w KX - 0 1").is_err(), "bad castling");
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w KK - 0 1").is_err(), "repeated castling");
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - e3 0 1").is_err(), "en passant on wrong rank");
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - z9 0 1").is_err(), "bad en passant square");
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - x 1").is_err(), "bad halfmove clock");
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 0").is_err(), "bad fullmove number");
+    }
+
+    #[test]
+    fn test_is_square_attacked() {
+        let board = get_starting_board();
+        assert!(board.is_square_attacked(Square::F3, Color::White));   // pawns and knight
+        assert!(board.is_square_attacked(Square::D2, Color::White));   // defended own piece
+        assert!(!board.is_square_attacked(Square::E4, Color::White));
+        assert!(board.is_square_attacked(Square::F6, Color::Black));
+        assert!(!board.is_square_attacked(Square::E5, Color::Black));
+
+        let board = Board::from_fen("4k3/8/8/8/1b6/8/8/R3K3 w - - 0 1").unwrap();
+        assert!(board.is_square_attacked(Square::E1, Color::Black));   // bishop on the diagonal
+        assert!(board.is_square_attacked(Square::A8, Color::White));   // rook up the open file
+        assert!(!board.is_square_attacked(Square::H4, Color::Black));
+    }
+
+    #[test]
+    fn test_castling_moves_generated() {
+        let board = Board::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1").unwrap();
+        let moves = board.get_next_moves(-1);
+        assert!(moves.contains(&"e1g1".to_string()));
+        assert!(moves.contains(&"e1c1".to_string()));
+
+        let board = Board::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b KQkq - 0 1").unwrap();
+        let moves = board.get_next_moves(-1);
+        assert!(moves.contains(&"e8g8".to_string()));
+        assert!(moves.contains(&"e8c8".to_string()));
+    }
+
+    #[test]
+    fn test_castling_requires_rights_and_empty_path() {
+        let board = Board::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w Qkq - 0 1").unwrap();
+        let moves = board.get_next_moves(-1);
+        assert!(!moves.contains(&"e1g1".to_string()), "kingside right was lost");
+        assert!(moves.contains(&"e1c1".to_string()));
+
+        let board = Board::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/RN2K1NR w KQkq - 0 1").unwrap();
+        let moves = board.get_next_moves(-1);
+        assert!(!moves.contains(&"e1g1".to_string()), "g1 is occupied");
+        assert!(!moves.contains(&"e1c1".to_string()), "b1 is occupied");
+    }
+
+    #[test]
+    fn test_castling_not_through_or_out_of_check() {
+        // Black rook on f8 covers f1, so white cannot castle kingside through it
+        let board = Board::from_fen("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let moves = board.get_next_moves(-1);
+        assert!(!moves.contains(&"e1g1".to_string()));
+        assert!(moves.contains(&"e1c1".to_string()));
+
+        // Black rook on e8 gives check, so neither castling is allowed
+        let board = Board::from_fen("4r1k1/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let moves = board.get_next_moves(-1);
+        assert!(!moves.contains(&"e1g1".to_string()));
+        assert!(!moves.contains(&"e1c1".to_string()));
+
+        // An attacked b1 does not matter for queenside castling
+        let board = Board::from_fen("1r2k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        assert!(board.get_next_moves(-1).contains(&"e1c1".to_string()));
+    }
+
+    #[test]
+    fn test_apply_castling_moves_rook() {
+        let mut board = Board::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1").unwrap();
+        board.apply_move_from_string("e1g1").unwrap();
+        assert_eq!(board.get_piece_at_coordinate("g1"), "♔");
+        assert_eq!(board.get_piece_at_coordinate("f1"), "♖");
+        assert_eq!(board.get_piece_at_coordinate("h1"), " ");
+        assert_eq!(board.castling_rights, BLACK_KINGSIDE | BLACK_QUEENSIDE);
+
+        board.apply_move_from_string("e8c8").unwrap();
+        assert_eq!(board.get_piece_at_coordinate("c8"), "♚");
+        assert_eq!(board.get_piece_at_coordinate("d8"), "♜");
+        assert_eq!(board.get_piece_at_coordinate("a8"), " ");
+        assert_eq!(board.castling_rights, 0);
+        assert_eq!(board.empty, !(board.any_white | board.any_black));
+    }
+
+    #[test]
+    fn test_apply_move_from_string_rejects_bad_moves() {
+        let mut board = get_starting_board();
+        assert_eq!(board.apply_move_from_string("e2"), Err("Invalid move format"));
+        assert_eq!(board.apply_move_from_string("e7e5"), Err("Illegal move"));
+        assert_eq!(board.apply_move_from_string("e2e5"), Err("Illegal move"));
+        assert_eq!(board, get_starting_board());
+
+        // The moves after one that cannot be played are not played either
+        let moves = ["e2e4", "e2e4", "e7e5"].iter().map(|s| s.to_string());
+        assert_eq!(board.apply_moves_from_strings(moves), Err("Illegal move"));
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    }
+
+    #[test]
+    fn test_castling_rights_lost_on_rook_move_and_capture() {
+        let mut board = Board::from_fen("r2bk2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        board.apply_move_from_string("a1a8").unwrap();  // rook takes rook, the bishop blocking the check
+        assert_eq!(board.castling_rights, WHITE_KINGSIDE | BLACK_KINGSIDE);
+
+        board.apply_move_from_string("h8h7").unwrap();
+        assert_eq!(board.castling_rights, WHITE_KINGSIDE);
+
+        board.apply_move_from_string("e1e2").unwrap();
+        assert_eq!(board.castling_rights, 0);
+    }
+
+    #[test]
+    fn test_double_push_sets_en_passant_square() {
+        let mut board = get_starting_board();
+        board.apply_move_from_string("e2e4").unwrap();
+        assert_eq!(board.en_passant, Some(Square::E3));
+
+        board.apply_move_from_string("g8f6").unwrap();
+        assert_eq!(board.en_passant, None);
+
+        board.apply_move_from_string("e4e5").unwrap();
+        board.apply_move_from_string("d7d5").unwrap();
+        assert_eq!(board.en_passant, Some(Square::D6));
+    }
+
+    #[test]
+    fn test_en_passant_capture_generated() {
+        let board = Board::from_fen("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1").unwrap();
+        assert!(board.get_next_moves(-1).contains(&"d5e6".to_string()));
+
+        let board = Board::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        assert!(board.get_next_moves(-1).contains(&"d4e3".to_string()));
+
+        // Without the en passant square the same capture is not available
+        let board = Board::from_fen("4k3/8/8/3Pp3/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(!board.get_next_moves(-1).contains(&"d5e6".to_string()));
+    }
+
+    #[test]
+    fn test_apply_en_passant_removes_captured_pawn() {
+        let mut board = Board::from_fen("4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1").unwrap();
+        board.apply_move_from_string("d7d5").unwrap();
+        board.apply_move_from_string("e5d6").unwrap();
+        assert_eq!(board.get_piece_at_coordinate("d6"), "♙");
+        assert_eq!(board.get_piece_at_coordinate("d5"), " ");
+        assert_eq!(board.black_pawns, 0);
+        assert_eq!(board.empty, !(board.any_white | board.any_black));
+
+        let mut board = Board::from_fen("4k3/8/8/8/5p2/8/6P1/4K3 w - - 0 1").unwrap();
+        board.apply_move_from_string("g2g4").unwrap();
+        board.apply_move_from_string("f4g3").unwrap();
+        assert_eq!(board.get_piece_at_coordinate("g3"), "♟");
+        assert_eq!(board.white_pawns, 0);
+    }
+
+    #[test]
+    fn test_pawn_captures_from_both_sides() {
+        // Both white pawns can capture on e5, and the a-file pawn must not wrap to the h-file
+        let board = Board::from_fen("4k3/8/8/4p2p/3P1P2/P7/8/4K3 w - - 0 1").unwrap();
+        let moves = board.get_next_moves(-1);
+        assert!(moves.contains(&"d4e5".to_string()));
+        assert!(moves.contains(&"f4e5".to_string()));
+        assert!(!moves.iter().any(|mv| mv.ends_with("h5")));
+
+        let moves = bitboard_to_pawn_capture_moves(Square::A3.to_bitboard(), Square::H4.to_bitboard() | Square::B4.to_bitboard(), false);
+        assert_eq!(moves, vec!["a3b4".to_string()]);
+    }
+
+    #[test]
+    fn test_move_promotion_parsing_and_display() {
+        let mv = Move::try_from("e7e8q").unwrap();
+        assert_eq!(mv.src, Square::E7);
+        assert_eq!(mv.target, Square::E8);
+        assert_eq!(mv.promotion, Some(PieceType::Queen));
+        assert_eq!(mv.to_string(), "e7e8q");
+
+        assert_eq!(Move::try_from("a2a1n").unwrap().promotion, Some(PieceType::Knight));
+        assert_eq!(Move::try_from("e2e4").unwrap().to_string(), "e2e4");
+        assert!(Move::try_from("e7e8k").is_err());
+        assert!(Move::try_from("e7e8qq").is_err());
+    }
+
+    #[test]
+    fn test_promotion_moves_generated() {
+        let board = Board::from_fen("3nk3/2P5/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let moves = board.get_next_moves(-1);
+        for mv in ["c7c8q", "c7c8r", "c7c8b", "c7c8n", "c7d8q", "c7d8r", "c7d8b", "c7d8n"] {
+            assert!(moves.contains(&mv.to_string()), "missing {}", mv);
+        }
+        assert!(!moves.contains(&"c7c8".to_string()));
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/p7/1N2K3 b - - 0 1").unwrap();
+        let moves = board.get_next_moves(-1);
+        for mv in ["a2a1q", "a2a1n", "a2b1q", "a2b1n"] {
+            assert!(moves.contains(&mv.to_string()), "missing {}", mv);
+        }
+    }
+
+    #[test]
+    fn test_apply_promotion() {
+        let mut board = Board::from_fen("3nk3/2P5/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        board.apply_move_from_string("c7d8n").unwrap();
+        assert_eq!(board.get_piece_at_coordinate("d8"), "♘");
+        assert_eq!(board.white_pawns, 0);
+        assert_eq!(board.black_knights, 0);
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/p7/4K3 b - - 0 1").unwrap();
+        board.apply_move_from_string("a2a1q").unwrap();
+        assert_eq!(board.get_piece_at_coordinate("a1"), "♛");
+        assert_eq!(board.black_pawns, 0);
+        assert_eq!(board.empty, !(board.any_white | board.any_black));
+    }
+
+    fn legal_move_strings(fen: &str) -> Vec<String> {
+        let mut moves: Vec<String> = Board::from_fen(fen).unwrap().legal_moves().iter().map(|mv| mv.to_string()).collect();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn test_legal_moves_reference_counts() {
+        assert_eq!(get_starting_board().legal_moves().len(), 20);
+        assert_eq!(legal_move_strings("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").len(), 48);
+        assert_eq!(legal_move_strings("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").len(), 14);
+        assert_eq!(legal_move_strings("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").len(), 6);
+        assert_eq!(legal_move_strings("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").len(), 44);
+    }
+
+    #[test]
+    fn test_pinned_piece_moves_along_pin() {
+        // The e-file rook is pinned by the black rook on e8 but may slide along the file
+        let moves = legal_move_strings("4r1k1/8/8/8/8/8/4R3/4K3 w - - 0 1");
+        let rook_moves: Vec<&String> = moves.iter().filter(|mv| mv.starts_with("e2")).collect();
+        assert_eq!(rook_moves, vec!["e2e3", "e2e4", "e2e5", "e2e6", "e2e7", "e2e8"]);
+
+        // A pinned knight can never move
+        let moves = legal_move_strings("6k1/8/8/b7/8/8/3N4/4K3 w - - 0 1");
+        assert!(!moves.iter().any(|mv| mv.starts_with("d2")));
+    }
+
+    #[test]
+    fn test_king_cannot_move_into_check() {
+        // The rook on d8 covers the d-file and the king may not retreat along the checking ray
+        let moves = legal_move_strings("3r2k1/8/8/8/8/8/8/4K2r w - - 0 1");
+        assert_eq!(moves, vec!["e1e2", "e1f2"]);
+    }
+
+    #[test]
+    fn test_check_evasions() {
+        // Queen on e5 checks along the e-file: block with the bishop, capture with the knight, or move the king
+        let moves = legal_move_strings("4k3/8/8/4q3/8/3N4/8/2B1K3 w - - 0 1");
+        assert_eq!(moves, vec!["c1e3", "d3e5", "e1d1", "e1d2", "e1f1", "e1f2"]);
+    }
+
+    #[test]
+    fn test_double_check_only_king_moves() {
+        let moves = legal_move_strings("4k3/8/8/8/1b6/8/4r3/R3K3 w - - 0 1");
+        assert!(moves.iter().all(|mv| mv.starts_with("e1")), "{:?}", moves);
+        assert!(!moves.is_empty());
+    }
+
+    #[test]
+    fn test_en_passant_exposing_king_is_illegal() {
+        // Capturing en passant would clear both pawns off the fifth rank and expose the king to the rook
+        let moves = legal_move_strings("8/8/8/KPp4r/8/8/8/7k w - c6 0 1");
+        assert!(!moves.contains(&"b5c6".to_string()));
+
+        // But it is the only way to get out of a check given by the double pushed pawn
+        let moves = legal_move_strings("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1");
+        assert!(moves.contains(&"e4d3".to_string()));
+    }
+
+    #[test]
+    fn test_is_check() {
+        assert!(!get_starting_board().is_check());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K2r w - - 0 1").unwrap().is_check());
+        assert!(Board::from_fen("4k3/8/8/8/8/5n2/8/4K3 w - - 0 1").unwrap().is_check());
+        assert!(Board::from_fen("4k3/3P4/8/8/8/8/8/4K3 b - - 0 1").unwrap().is_check());
+        assert!(!Board::from_fen("4k3/4P3/8/8/8/8/8/4K3 b - - 0 1").unwrap().is_check());
+    }
+
+    #[test]
+    fn test_checkmate() {
+        let board = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert!(board.is_check());
+        assert!(board.is_checkmate());
+        assert!(!board.is_stalemate());
+        assert_eq!(board.game_result(), Some(GameResult::Checkmate { winner: Color::Black }));
+        assert_eq!(board.game_result().unwrap().winner(), Some(Color::Black));
+        assert_eq!(board.get_next_move(), NULL_MOVE);
+
+        // Back rank mate against black
+        let board = Board::from_fen("3R2k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(board.game_result(), Some(GameResult::Checkmate { winner: Color::White }));
+    }
+
+    #[test]
+    fn test_move_clocks() {
+        let mut board = get_starting_board();
+        board.apply_moves_from_strings(["g1f3", "g8f6", "f3g1"].iter().map(|s| s.to_string())).unwrap();
+        assert_eq!((board.halfmove_clock(), board.fullmove_number()), (3, 2));
+        // Pawn moves and captures restart the count
+        board.apply_move_from_string("e7e5").unwrap();
+        assert_eq!((board.halfmove_clock(), board.fullmove_number()), (0, 3));
+        board.apply_moves_from_strings(["b1c3", "f6e4", "c3e4"].iter().map(|s| s.to_string())).unwrap();
+        assert_eq!((board.halfmove_clock(), board.fullmove_number()), (0, 4));
+        assert_eq!(board.to_fen(), "rnbqkb1r/pppp1ppp/8/4p3/4N3/8/PPPPPPPP/R1BQKBNR b KQkq - 0 4");
+
+        let mut board = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 12 40").unwrap();
+        board.apply_move_from_string("e5d6").unwrap();
+        assert_eq!((board.halfmove_clock(), board.fullmove_number()), (0, 40));
+    }
+
+    #[test]
+    fn test_fifty_move_rule() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+        assert!(!board.is_fifty_move_draw());
+        assert_eq!(board.game_result(), None);
+        let undo = board.make_move(&Move::new(Square::A1, Square::A2));
+        assert!(board.is_fifty_move_draw());
+        assert_eq!(board.game_result(), Some(GameResult::FiftyMoveRule));
+        assert_eq!(GameResult::FiftyMoveRule.winner(), None);
+        board.unmake_move(undo);
+        assert_eq!(board.halfmove_clock(), 99);
+
+        // Mate on the hundredth halfmove still counts
+        let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80").unwrap();
+        board.apply_move_from_string("a1a8").unwrap();
+        assert_eq!(board.game_result(), Some(GameResult::Checkmate { winner: Color::White }));
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let mut board = get_starting_board();
+        assert!(board.is_repetition(1));
+        assert!(!board.is_repetition(2));
+        board.apply_moves_from_strings(shuffle.iter().map(|s| s.to_string())).unwrap();
+        assert!(board.is_repetition(2));
+        assert_eq!(board.game_result(), None);
+        board.apply_moves_from_strings(shuffle.iter().map(|s| s.to_string())).unwrap();
+        assert!(board.is_repetition(3));
+        assert_eq!(board.game_result(), Some(GameResult::ThreefoldRepetition));
+        assert_eq!(GameResult::ThreefoldRepetition.winner(), None);
+
+        // Taking a move back forgets it was played
+        let undo = board.make_move(&Move::new(Square::G1, Square::F3));
+        board.unmake_move(undo);
+        assert!(board.is_repetition(3));
+
+        // A pawn move in between means the earlier positions cannot come back
+        let mut board = get_starting_board();
+        board.apply_moves_from_strings(["g1f3", "g8f6", "f3g1", "f6g8", "e2e3", "e7e6"].iter().map(|s| s.to_string())).unwrap();
+        board.apply_moves_from_strings(shuffle.iter().map(|s| s.to_string())).unwrap();
+        assert!(board.is_repetition(2));
+        assert!(!board.is_repetition(3));
+
+        // and are dropped from the history, until the pawn move is taken back
+        let before = board.clone();
+        let undo = board.make_move(&Move::new(Square::D2, Square::D4));
+        assert!(board.history.is_empty());
+        board.unmake_move(undo);
+        assert_eq!(board, before);
+    }
+
+    #[test]
+    fn test_stalemate() {
+        let board = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(!board.is_check());
+        assert!(board.is_stalemate());
+        assert!(!board.is_checkmate());
+        assert_eq!(board.game_result(), Some(GameResult::Stalemate));
+        assert_eq!(GameResult::Stalemate.winner(), None);
+        assert_eq!(board.get_next_move(), NULL_MOVE);
+    }
+
+    #[test]
+    fn test_game_in_progress_has_no_result() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2r w - - 0 1").unwrap();
+        assert!(board.is_check());
+        assert!(!board.is_checkmate());
+        assert_eq!(board.game_result(), None);
+    }
+
+    #[test]
+    fn test_to_fen_starting_position() {
+        assert_eq!(get_starting_board().to_fen(), STARTING_FEN);
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        let fens = [
+            STARTING_FEN,
+            "rnbqkbnr/pp1ppppp/8/2pP4/8/8/PPP1PPPP/RNBQKBNR w Kq c6 3 7",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4K3 b - - 49 120",
+        ];
+        for fen in fens {
+            let board: Board = fen.parse().unwrap();
+            assert_eq!(board.to_fen(), fen);
+            assert_eq!(board.to_string(), fen);
+        }
+    }
+
+    #[test]
+    fn test_legal_captures_match_filtered_legal_moves() {
+        let fens = [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            // En passant, and a check the rook can answer by taking the knight
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+            "4k3/8/8/8/8/5n2/8/4KR2 w - - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            let mut expected: Vec<Move> = board.legal_moves().into_iter()
+                .filter(|mv| board.is_capture(mv) || mv.promotion.is_some())
+                .collect();
+            let mut captures = board.legal_captures();
+            captures.sort_by_key(|mv| mv.to_string());
+            expected.sort_by_key(|mv| mv.to_string());
+            assert_eq!(captures, expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_unmake_special_moves() {
+        let cases = [
+            // Castling on both wings
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1"),
+            ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8"),
+            // En passant
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"),
+            // Capturing underpromotion that also removes a castling right
+            ("r3k3/1P6/8/8/8/8/8/4K3 w q - 5 30", "b7a8n"),
+            // Ordinary capture
+            ("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5"),
+        ];
+        for (fen, mv) in cases {
+            let mut board = Board::from_fen(fen).unwrap();
+            let undo = board.make_move(&Move::try_from(mv).unwrap());
+            assert_ne!(board.to_fen(), fen);
+            board.unmake_move(undo);
+            assert_eq!(board, Board::from_fen(fen).unwrap(), "{} on {}", mv, fen);
+        }
+    }
+
+    #[test]
+    fn test_to_fen_after_move() {
+        let mut board = get_starting_board();
+        board.apply_move(&Move::new(Square::G1, Square::F3));
+        assert!(board.to_fen().starts_with("rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq -"));
+    }
 }
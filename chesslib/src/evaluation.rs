@@ -0,0 +1,321 @@
+use crate::board::{Board, Color, PieceType};
+
+/// Piece values in centipawns in the middlegame
+pub const PAWN_VALUE: i32 = 100;
+pub const KNIGHT_VALUE: i32 = 320;
+pub const BISHOP_VALUE: i32 = 330;
+pub const ROOK_VALUE: i32 = 500;
+pub const QUEEN_VALUE: i32 = 900;
+
+/// Piece values in centipawns in the endgame, where pawns and rooks gain and minor pieces lose
+pub const PAWN_VALUE_ENDGAME: i32 = 120;
+pub const KNIGHT_VALUE_ENDGAME: i32 = 300;
+pub const BISHOP_VALUE_ENDGAME: i32 = 320;
+pub const ROOK_VALUE_ENDGAME: i32 = 530;
+pub const QUEEN_VALUE_ENDGAME: i32 = 940;
+
+/// Middlegame value of a piece, with the king counted as worth nothing
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => PAWN_VALUE,
+        PieceType::Knight => KNIGHT_VALUE,
+        PieceType::Bishop => BISHOP_VALUE,
+        PieceType::Rook => ROOK_VALUE,
+        PieceType::Queen => QUEEN_VALUE,
+        PieceType::King => 0,
+    }
+}
+
+/// Game phase with all minor and major pieces on the board; a bare board is phase 0
+pub const MAX_PHASE: i32 = 24;
+
+// Contribution of each piece to the game phase
+const KNIGHT_PHASE: i32 = 1;
+const BISHOP_PHASE: i32 = 1;
+const ROOK_PHASE: i32 = 2;
+const QUEEN_PHASE: i32 = 4;
+
+// Middlegame piece-square tables from White's point of view, laid out as seen from White's side
+// of the board: the first row is rank 8, the last row rank 1. Black uses the same tables
+// mirrored. Pieces other than pawns and kings use them in the endgame too.
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+// In the endgame passed pawns are worth pushing whatever their file
+const PAWN_ENDGAME_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    80, 80, 80, 80, 80, 80, 80, 80,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    15, 15, 15, 15, 15, 15, 15, 15,
+     5,  5,  5,  5,  5,  5,  5,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+// With the queens gone the king becomes a fighting piece and belongs in the centre
+const KING_ENDGAME_TABLE: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+// Midgame and endgame value and table of one kind of piece
+struct PieceTerms {
+    value: i32,
+    value_endgame: i32,
+    table: &'static [i32; 64],
+    table_endgame: &'static [i32; 64],
+}
+
+const PAWN_TERMS: PieceTerms = PieceTerms {
+    value: PAWN_VALUE, value_endgame: PAWN_VALUE_ENDGAME, table: &PAWN_TABLE, table_endgame: &PAWN_ENDGAME_TABLE,
+};
+const KNIGHT_TERMS: PieceTerms = PieceTerms {
+    value: KNIGHT_VALUE, value_endgame: KNIGHT_VALUE_ENDGAME, table: &KNIGHT_TABLE, table_endgame: &KNIGHT_TABLE,
+};
+const BISHOP_TERMS: PieceTerms = PieceTerms {
+    value: BISHOP_VALUE, value_endgame: BISHOP_VALUE_ENDGAME, table: &BISHOP_TABLE, table_endgame: &BISHOP_TABLE,
+};
+const ROOK_TERMS: PieceTerms = PieceTerms {
+    value: ROOK_VALUE, value_endgame: ROOK_VALUE_ENDGAME, table: &ROOK_TABLE, table_endgame: &ROOK_TABLE,
+};
+const QUEEN_TERMS: PieceTerms = PieceTerms {
+    value: QUEEN_VALUE, value_endgame: QUEEN_VALUE_ENDGAME, table: &QUEEN_TABLE, table_endgame: &QUEEN_TABLE,
+};
+const KING_TERMS: PieceTerms = PieceTerms {
+    value: 0, value_endgame: 0, table: &KING_TABLE, table_endgame: &KING_ENDGAME_TABLE,
+};
+
+// Midgame and endgame sums of the piece value plus its table entry over every piece on the
+// bitboard. Table rows run from rank 8 down, so a White square is found by flipping the rank
+// of its bit index.
+fn score_pieces(mut pieces: u64, terms: &PieceTerms, color: Color) -> (i32, i32) {
+    let (mut midgame, mut endgame) = (0, 0);
+    while pieces != 0 {
+        let square = pieces.trailing_zeros() as usize;
+        pieces &= pieces - 1;
+        let index = match color {
+            Color::White => square ^ 56,
+            Color::Black => square,
+        };
+        midgame += terms.value + terms.table[index];
+        endgame += terms.value_endgame + terms.table_endgame[index];
+    }
+    (midgame, endgame)
+}
+
+fn score_side(board: &Board, color: Color) -> (i32, i32) {
+    let (pawns, knights, bishops, rooks, queens, king) = match color {
+        Color::White => (board.white_pawns, board.white_knights, board.white_bishops,
+                         board.white_rooks, board.white_queen, board.white_king),
+        Color::Black => (board.black_pawns, board.black_knights, board.black_bishops,
+                         board.black_rooks, board.black_queen, board.black_king),
+    };
+    [
+        score_pieces(pawns, &PAWN_TERMS, color),
+        score_pieces(knights, &KNIGHT_TERMS, color),
+        score_pieces(bishops, &BISHOP_TERMS, color),
+        score_pieces(rooks, &ROOK_TERMS, color),
+        score_pieces(queens, &QUEEN_TERMS, color),
+        score_pieces(king, &KING_TERMS, color),
+    ]
+    .iter()
+    .fold((0, 0), |(midgame, endgame), (mg, eg)| (midgame + mg, endgame + eg))
+}
+
+/// How far the game is from the endgame, from `MAX_PHASE` with all pieces on the board down
+/// to 0 with only kings and pawns left
+///
+/// Promotions can push the raw count past `MAX_PHASE`, so it is capped there.
+pub fn game_phase(board: &Board) -> i32 {
+    let count = |bitboard: u64| bitboard.count_ones() as i32;
+    let phase = KNIGHT_PHASE * count(board.white_knights | board.black_knights)
+        + BISHOP_PHASE * count(board.white_bishops | board.black_bishops)
+        + ROOK_PHASE * count(board.white_rooks | board.black_rooks)
+        + QUEEN_PHASE * count(board.white_queen | board.black_queen);
+    phase.min(MAX_PHASE)
+}
+
+/// Static evaluation of the position in centipawns from the side to move's point of view
+///
+/// Counts material and rewards pieces standing on good squares for their type. Every term has
+/// a middlegame and an endgame weight, blended according to the `game_phase` so that, for
+/// example, the king shelters early on and walks to the centre once the pieces come off.
+///
+/// # Examples
+///
+/// ```
+/// use chesslib::board::{get_starting_board, Board};
+/// use chesslib::evaluation::evaluate;
+///
+/// assert_eq!(evaluate(&get_starting_board()), 0);
+/// // White is a queen up, which is good for White and bad for Black to move
+/// let white_to_move = Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+/// let black_to_move = Board::from_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+/// assert!(evaluate(&white_to_move) > 800);
+/// assert_eq!(evaluate(&black_to_move), -evaluate(&white_to_move));
+/// ```
+pub fn evaluate(board: &Board) -> i32 {
+    let (white_midgame, white_endgame) = score_side(board, Color::White);
+    let (black_midgame, black_endgame) = score_side(board, Color::Black);
+    let phase = game_phase(board);
+    let score = ((white_midgame - black_midgame) * phase
+        + (white_endgame - black_endgame) * (MAX_PHASE - phase)) / MAX_PHASE;
+    match board.side_to_move {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Flips the board top to bottom and swaps the colours of all pieces, keeping the side to
+    // move, so the result is the same position with the roles of the players exchanged
+    fn swap_colors(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let placement: Vec<String> = fields[0].split('/').rev()
+            .map(|rank| rank.chars().map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect())
+            .collect();
+        format!("{} {} - - 0 1", placement.join("/"), fields[1])
+    }
+
+    fn flip_side(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        format!("{} {} - - 0 1", fields[0], side)
+    }
+
+    const POSITIONS: [&str; 5] = [
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    ];
+
+    #[test]
+    fn test_swapped_colors_score_as_negation() {
+        for fen in POSITIONS {
+            let board = Board::from_fen(fen).unwrap();
+            let swapped = Board::from_fen(&swap_colors(fen)).unwrap();
+            assert_eq!(evaluate(&swapped), -evaluate(&board), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_mirrored_position_scores_the_same_for_the_other_side() {
+        for fen in POSITIONS {
+            let board = Board::from_fen(fen).unwrap();
+            let mirrored = Board::from_fen(&flip_side(&swap_colors(fen))).unwrap();
+            assert_eq!(evaluate(&mirrored), evaluate(&board), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_piece_squares_matter() {
+        let centralized = Board::from_fen("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+        let cornered = Board::from_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+        assert!(evaluate(&centralized) > evaluate(&cornered));
+
+        let advanced = Board::from_fen("4k3/8/3P4/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let home = Board::from_fen("4k3/8/8/8/8/8/3P4/4K3 w - - 0 1").unwrap();
+        assert!(evaluate(&advanced) > evaluate(&home));
+    }
+
+    #[test]
+    fn test_game_phase() {
+        assert_eq!(game_phase(&Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap()), MAX_PHASE);
+        assert_eq!(game_phase(&Board::from_fen("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1").unwrap()), 0);
+        assert_eq!(game_phase(&Board::from_fen("r3k3/8/8/8/8/8/8/2B1K1N1 w - - 0 1").unwrap()), 4);
+        // Extra queens from promotion do not go past the maximum
+        assert_eq!(game_phase(&Board::from_fen("QQQQkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQk - 0 1").unwrap()), MAX_PHASE);
+    }
+
+    #[test]
+    fn test_king_shelters_in_middlegame_and_centralizes_in_endgame() {
+        let castled = Board::from_fen("rnbq1rk1/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w - - 0 1").unwrap();
+        let exposed = Board::from_fen("rnbq1rk1/pppppppp/8/8/4K3/8/PPPPPPPP/RNBQ1R2 w - - 0 1").unwrap();
+        assert!(evaluate(&castled) > evaluate(&exposed));
+
+        let corner = Board::from_fen("6k1/pppp4/8/8/8/8/4PPPP/7K w - - 0 1").unwrap();
+        let centre = Board::from_fen("6k1/pppp4/8/8/4K3/8/4PPPP/8 w - - 0 1").unwrap();
+        assert!(evaluate(&centre) > evaluate(&corner));
+    }
+
+    #[test]
+    fn test_material_dominates() {
+        let rook_up = Board::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/R3K3 w - - 0 1").unwrap();
+        assert!(evaluate(&rook_up) > 400);
+    }
+}
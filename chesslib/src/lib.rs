@@ -1,7 +1,16 @@
 pub mod board;
+pub mod evaluation;
+pub mod logger;
 pub mod move_generation;
+pub mod move_ordering;
+pub mod options;
+pub mod perft;
+pub mod search;
+pub mod time_manager;
+pub mod transposition;
 pub mod uci;
 pub mod types;
+pub mod zobrist;
 
 pub use uci::handle_uci_command;
 pub use types::Square;
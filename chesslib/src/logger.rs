@@ -0,0 +1,131 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable naming a file to log UCI traffic to, unless the command line names one
+pub const LOG_FILE_ENV: &str = "CHESS_ENGINE_LOG";
+
+/// Log of the UCI conversation, every line stamped with the time and marked `>>` when it came
+/// from the GUI, `<<` when the engine sent it, or `--` for notes of the engine's own
+///
+/// Logging is off until a file is opened.
+#[derive(Debug, Default)]
+pub struct Logger {
+    file: Option<File>,
+}
+
+impl Logger {
+    pub fn new() -> Logger {
+        Logger::default()
+    }
+
+    /// Logs to the end of the file at `path`, creating it if needed, or stops logging if
+    /// `path` is empty
+    pub fn open(&mut self, path: &str) -> io::Result<()> {
+        self.file = None;
+        if !path.is_empty() {
+            self.file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Logs a line received from the GUI
+    pub fn input(&mut self, line: &str) {
+        self.write(">>", line);
+    }
+
+    /// Logs a line sent to the GUI
+    pub fn output(&mut self, line: &str) {
+        self.write("<<", line);
+    }
+
+    /// Logs a note that is not part of the conversation, such as the position set up
+    pub fn note(&mut self, line: &str) {
+        self.write("--", line);
+    }
+
+    // A log that can no longer be written to is given up rather than disturbing the game
+    fn write(&mut self, marker: &str, line: &str) {
+        if let Some(file) = &mut self.file {
+            if writeln!(file, "{} {} {}", timestamp(SystemTime::now()), marker, line).is_err() {
+                self.file = None;
+            }
+        }
+    }
+}
+
+// UTC time as `YYYY-MM-DD hh:mm:ss.mmm`
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds_of_day = seconds % 86_400;
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}", year, month, day,
+        seconds_of_day / 3600, seconds_of_day / 60 % 60, seconds_of_day % 60, since_epoch.subsec_millis())
+}
+
+// Date of the day `days` after 1970-01-01 in the proleptic Gregorian calendar, after Howard
+// Hinnant's algorithm, which counts in 400 year eras starting on 1 March
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01 00:00:00.000");
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_millis(951_782_400_250)), "2000-02-29 00:00:00.250");
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(1_792_325_045)), "2026-10-18 12:04:05.000");
+    }
+
+    #[test]
+    fn test_logs_with_direction_markers() {
+        let path = std::env::temp_dir().join(format!("chesslib_logger_test_{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut logger = Logger::new();
+        logger.input("not logged while off");
+        logger.open(path.to_str().unwrap()).unwrap();
+        assert!(logger.is_enabled());
+        logger.input("isready");
+        logger.output("readyok");
+        logger.note("Position: 8/8/8/8/8/8/8/8 w - - 0 1");
+        logger.open("").unwrap();
+        assert!(!logger.is_enabled());
+        logger.output("not logged either");
+
+        let log = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with(" >> isready"), "Got {}", lines[0]);
+        assert!(lines[1].ends_with(" << readyok"), "Got {}", lines[1]);
+        assert!(lines[2].ends_with(" -- Position: 8/8/8/8/8/8/8/8 w - - 0 1"), "Got {}", lines[2]);
+        // The timestamp is `YYYY-MM-DD hh:mm:ss.mmm`
+        assert_eq!(lines[0].len(), "2026-10-18 12:04:05.000 >> isready".len());
+    }
+
+    #[test]
+    fn test_open_reports_errors() {
+        let mut logger = Logger::new();
+        assert!(logger.open("/nonexistent/directory/engine.log").is_err());
+        assert!(!logger.is_enabled());
+    }
+}
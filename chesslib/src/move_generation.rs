@@ -1,3 +1,5 @@
+use lazy_static::lazy_static;
+
 // File masks to prevent wrapping around the board edges
 const NOT_A_FILE: u64 = 0xfefefefefefefefe;  // ~(0x0101010101010101)
 const NOT_H_FILE: u64 = 0x7f7f7f7f7f7f7f7f;  // ~(0x8080808080808080)
@@ -95,72 +97,182 @@ pub fn knight_attack_targets(knights: u64, enemy_pieces: u64) -> u64 {
     knight_moves(knights) & enemy_pieces
 }
 
-// Get bishop moves - handling all 4 diagonal directions
-pub fn bishop_moves(bishops: u64, friendly_pieces: u64, enemy_pieces: u64) -> u64 {
-    let mut moves = 0u64;
-    let mut working_bishops = bishops;
-
-    while working_bishops != 0 {
-        let bishop_pos = working_bishops.trailing_zeros() as u8;
-        working_bishops &= working_bishops - 1;  // Clear the processed bit
-
-        // Northeast diagonal
-        let mut pos = bishop_pos;
-        while pos % 8 != 7 && pos < 56 { // While not on h-file and not on rank 8
-            pos += 9;
-            let target = 1u64 << pos;
-            if friendly_pieces & target != 0 {
-                break; // Stop at friendly piece
-            }
-            moves |= target;  // Add this square as a valid move
-            if enemy_pieces & target != 0 {
-                break; // Stop after capturing enemy piece
+// Sliding piece attacks are looked up in tables indexed by "magic" multiplication: the
+// occupancy of the squares that can block a piece is multiplied by a per-square constant
+// chosen so the top bits of the product form a collision free index into that square's
+// attack sets. The tables are filled once, on first use, from the ray walking reference
+// implementation below.
+
+// (file, rank) steps of the diagonal and orthogonal directions
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+
+// Walks each ray from the square until it leaves the board or reaches an occupied square,
+// which is included since it may hold an enemy piece to capture
+fn ray_attacks(square: u8, occupied: u64, directions: &[(i8, i8); 4]) -> u64 {
+    let mut attacks = 0u64;
+    for &(file_step, rank_step) in directions {
+        let mut file = (square % 8) as i8 + file_step;
+        let mut rank = (square / 8) as i8 + rank_step;
+        while (0..8).contains(&file) && (0..8).contains(&rank) {
+            let target = 1u64 << (rank * 8 + file);
+            attacks |= target;
+            if occupied & target != 0 {
+                break;
             }
+            file += file_step;
+            rank += rank_step;
         }
+    }
+    attacks
+}
 
-        // Southeast diagonal
-        pos = bishop_pos;
-        while pos % 8 != 7 && pos >= 8 { // While not on h-file and not on rank 1
-            pos -= 7;
-            let target = 1u64 << pos;
-            if friendly_pieces & target != 0 {
-                break; // Stop at friendly piece
-            }
-            moves |= target;  // Add this square as a valid move
-            if enemy_pieces & target != 0 {
-                break; // Stop after capturing enemy piece
-            }
-        }
+// Reference bishop attacks by walking the diagonals, used to build the magic tables
+pub fn bishop_ray_attacks(square: u8, occupied: u64) -> u64 {
+    ray_attacks(square, occupied, &BISHOP_DIRECTIONS)
+}
 
-        // Southwest diagonal
-        pos = bishop_pos;
-        while pos % 8 != 0 && pos >= 8 { // While not on a-file and not on rank 1
-            pos -= 9;
-            let target = 1u64 << pos;
-            if friendly_pieces & target != 0 {
-                break; // Stop at friendly piece
-            }
-            moves |= target;  // Add this square as a valid move
-            if enemy_pieces & target != 0 {
-                break; // Stop after capturing enemy piece
-            }
+// Reference rook attacks by walking the ranks and files, used to build the magic tables
+pub fn rook_ray_attacks(square: u8, occupied: u64) -> u64 {
+    ray_attacks(square, occupied, &ROOK_DIRECTIONS)
+}
+
+// Squares whose occupancy changes a slider's attacks: every ray square except the last one
+// before the edge, since a piece there blocks nothing further
+fn relevant_occupancy_mask(square: u8, directions: &[(i8, i8); 4]) -> u64 {
+    let mut mask = 0u64;
+    for &(file_step, rank_step) in directions {
+        let mut file = (square % 8) as i8 + file_step;
+        let mut rank = (square / 8) as i8 + rank_step;
+        while (0..8).contains(&(file + file_step)) && (0..8).contains(&(rank + rank_step)) {
+            mask |= 1u64 << (rank * 8 + file);
+            file += file_step;
+            rank += rank_step;
         }
+    }
+    mask
+}
 
-        // Northwest diagonal
-        pos = bishop_pos;
-        while pos % 8 != 0 && pos < 56 { // While not on a-file and not on rank 8
-            pos += 7;
-            let target = 1u64 << pos;
-            if friendly_pieces & target != 0 {
-                break; // Stop at friendly piece
-            }
-            moves |= target;  // Add this square as a valid move
-            if enemy_pieces & target != 0 {
-                break; // Stop after capturing enemy piece
+// Magic multipliers per square, found offline by trying sparse random numbers until one
+// gave a collision free mapping for every occupancy of the square's mask
+const BISHOP_MAGICS: [u64; 64] = [
+    0x3304200800410040, 0x0208504440404200, 0x080401020202C200, 0x4004042A80400000,
+    0x84020210A1005100, 0x0089040340400026, 0x1004010150110808, 0x8401140406021080,
+    0x1808282044044240, 0xD12010044804A120, 0x10000802404E0000, 0x000A480608410000,
+    0x001104042000020C, 0x2010008804404000, 0x0804209808080404, 0x4000145108011005,
+    0x02C0112004048080, 0x0220040888210040, 0x001000E240818101, 0x000800A101410108,
+    0x0014080080A00032, 0x2818420200500440, 0x0004000084044308, 0x0044200201110803,
+    0x8020040008080844, 0x0141104120048120, 0x60C2110008080020, 0x100C040008012044,
+    0x8201020014008400, 0x000101002E048885, 0x01010C1401008800, 0x018A002004410800,
+    0x1001202020100444, 0x0007105808520850, 0x0020404051480200, 0x08044008200A0200,
+    0x780C030045040040, 0x0120081880004040, 0x0004010048040440, 0x18042404300A4100,
+    0x400108208500108C, 0x0000808808032102, 0x0D82082088001000, 0x300011201104D800,
+    0x0000600410101100, 0x4024088408100101, 0x1A28280840820840, 0x0018064040480200,
+    0x0022010160100000, 0x8004404804100040, 0x1000444208040442, 0x0448004884110080,
+    0x284000D002120000, 0x0008912021830100, 0x6008080838006000, 0x9104100202082180,
+    0x7000434804104200, 0x04000200440A3890, 0x08008B8026081200, 0x0000002901840450,
+    0x800C106060443401, 0x0300084002048101, 0x0400101010208080, 0x2088020400440100,
+];
+
+const ROOK_MAGICS: [u64; 64] = [
+    0x208000400A82B060, 0x1080200080104000, 0x0180100020018882, 0x1080100080040800,
+    0x0480040002080081, 0x0200018810440200, 0x0200044081020008, 0x06800042A1800500,
+    0x0102800084204000, 0x4448802008400081, 0x0008802000100080, 0x0020040201004081,
+    0x0006002200040810, 0x2601000300080400, 0x5001000402000100, 0x04210003000281DA,
+    0x0000208000400082, 0x0442020020410081, 0x9800110020050042, 0x00B0818018003000,
+    0x0002020008200510, 0x1010808002000400, 0x2000840001480250, 0x01E0020001004084,
+    0x0602004200210080, 0x11005000C000E000, 0x0440190100200040, 0x0000080080801000,
+    0x0020080080040080, 0x4060040080020080, 0x0120088400021001, 0x0A00008200204514,
+    0x0A00408001002100, 0x0200201000404000, 0x0190002800200400, 0x4084411202000A20,
+    0x0208080080800401, 0x0080040080800200, 0x8010800100800200, 0x400009A242001104,
+    0x1880104020084000, 0x0110402010004004, 0x0080820014420020, 0x0080100300210008,
+    0x0084000800048080, 0x120A000804020010, 0x0000010208040010, 0x0004004889020004,
+    0x0002800240002480, 0xE408200040009080, 0x1100100080200080, 0x0028201000090100,
+    0x1843000408001100, 0x1202000804100200, 0x6000800100020080, 0x8002010E4C208200,
+    0x8088910280002441, 0x0401002010488202, 0x051040820022908A, 0x021200402004100A,
+    0x0002008810200502, 0x0002001004814802, 0x0178100821209204, 0x0081022409008442,
+];
+
+struct Magic {
+    mask: u64,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+struct MagicTable {
+    magics: Vec<Magic>,
+    attacks: Vec<u64>,
+}
+
+impl MagicTable {
+    fn new(directions: &[(i8, i8); 4], magic_numbers: &[u64; 64]) -> MagicTable {
+        let mut magics = Vec::with_capacity(64);
+        let mut attacks = Vec::new();
+
+        for square in 0..64u8 {
+            let mask = relevant_occupancy_mask(square, directions);
+            let bits = mask.count_ones();
+            let magic = Magic { mask, magic: magic_numbers[square as usize], shift: 64 - bits, offset: attacks.len() };
+            let mut table = vec![None; 1usize << bits];
+
+            // Enumerate every subset of the mask with the carry-rippler trick
+            let mut occupancy = 0u64;
+            loop {
+                let index = (occupancy.wrapping_mul(magic.magic) >> magic.shift) as usize;
+                let attack = ray_attacks(square, occupancy, directions);
+                // Two occupancies may share a slot only if they give the same attacks
+                debug_assert!(table[index].is_none_or(|existing| existing == attack),
+                    "magic for square {} maps different attacks to slot {}", square, index);
+                table[index] = Some(attack);
+                occupancy = occupancy.wrapping_sub(mask) & mask;
+                if occupancy == 0 {
+                    break;
+                }
             }
+
+            attacks.extend(table.into_iter().map(|attack| attack.unwrap_or(0)));
+            magics.push(magic);
         }
+
+        MagicTable { magics, attacks }
     }
-    moves
+
+    fn attacks(&self, square: u8, occupied: u64) -> u64 {
+        let magic = &self.magics[square as usize];
+        let index = ((occupied & magic.mask).wrapping_mul(magic.magic) >> magic.shift) as usize;
+        self.attacks[magic.offset + index]
+    }
+}
+
+lazy_static! {
+    static ref BISHOP_TABLE: MagicTable = MagicTable::new(&BISHOP_DIRECTIONS, &BISHOP_MAGICS);
+    static ref ROOK_TABLE: MagicTable = MagicTable::new(&ROOK_DIRECTIONS, &ROOK_MAGICS);
+}
+
+// Squares attacked by a bishop on the given square, including the first blocker on each diagonal
+pub fn bishop_attacks(square: u8, occupied: u64) -> u64 {
+    BISHOP_TABLE.attacks(square, occupied)
+}
+
+// Squares attacked by a rook on the given square, including the first blocker on each line
+pub fn rook_attacks(square: u8, occupied: u64) -> u64 {
+    ROOK_TABLE.attacks(square, occupied)
+}
+
+// Get bishop moves - handling all 4 diagonal directions
+pub fn bishop_moves(bishops: u64, friendly_pieces: u64, enemy_pieces: u64) -> u64 {
+    let occupied = friendly_pieces | enemy_pieces;
+    let mut moves = 0u64;
+    let mut working_bishops = bishops;
+
+    while working_bishops != 0 {
+        let bishop_pos = working_bishops.trailing_zeros() as u8;
+        working_bishops &= working_bishops - 1;  // Clear the processed bit
+        moves |= bishop_attacks(bishop_pos, occupied);
+    }
+    // Blockers are included in the attacks, keep only the enemy ones as captures
+    moves & !friendly_pieces
 }
 
 // Legal moves are already computed in bishop_moves
@@ -168,72 +280,24 @@ pub fn bishop_legal_moves(bishops: u64, friendly_pieces: u64, enemy_pieces: u64)
     bishop_moves(bishops, friendly_pieces, enemy_pieces)
 }
 
+// Get bishop attack targets (squares with enemy pieces that can be captured)
+pub fn bishop_attack_targets(bishops: u64, friendly_pieces: u64, enemy_pieces: u64) -> u64 {
+    bishop_moves(bishops, friendly_pieces, enemy_pieces) & enemy_pieces
+}
+
 // Get rook moves - handling all 4 orthogonal directions
 pub fn rook_moves(rooks: u64, friendly_pieces: u64, enemy_pieces: u64) -> u64 {
+    let occupied = friendly_pieces | enemy_pieces;
     let mut moves = 0u64;
     let mut working_rooks = rooks;
 
     while working_rooks != 0 {
         let rook_pos = working_rooks.trailing_zeros() as u8;
         working_rooks &= working_rooks - 1;  // Clear the processed bit
-
-        // North (up)
-        let mut pos = rook_pos;
-        while pos < 56 { // While not on rank 8
-            pos += 8;
-            let target = 1u64 << pos;
-            if friendly_pieces & target != 0 {
-                break; // Stop at friendly piece
-            }
-            moves |= target;  // Add this square as a valid move
-            if enemy_pieces & target != 0 {
-                break; // Stop after capturing enemy piece
-            }
-        }
-
-        // South (down)
-        pos = rook_pos;
-        while pos >= 8 { // While not on rank 1
-            pos -= 8;
-            let target = 1u64 << pos;
-            if friendly_pieces & target != 0 {
-                break; // Stop at friendly piece
-            }
-            moves |= target;  // Add this square as a valid move
-            if enemy_pieces & target != 0 {
-                break; // Stop after capturing enemy piece
-            }
-        }
-
-        // East (right)
-        pos = rook_pos;
-        while pos % 8 != 7 { // While not on h-file
-            pos += 1;
-            let target = 1u64 << pos;
-            if friendly_pieces & target != 0 {
-                break; // Stop at friendly piece
-            }
-            moves |= target;  // Add this square as a valid move
-            if enemy_pieces & target != 0 {
-                break; // Stop after capturing enemy piece
-            }
-        }
-
-        // West (left)
-        pos = rook_pos;
-        while pos % 8 != 0 { // While not on a-file
-            pos -= 1;
-            let target = 1u64 << pos;
-            if friendly_pieces & target != 0 {
-                break; // Stop at friendly piece
-            }
-            moves |= target;  // Add this square as a valid move
-            if enemy_pieces & target != 0 {
-                break; // Stop after capturing enemy piece
-            }
-        }
+        moves |= rook_attacks(rook_pos, occupied);
     }
-    moves
+    // Blockers are included in the attacks, keep only the enemy ones as captures
+    moves & !friendly_pieces
 }
 
 // Legal moves are already computed in rook_moves
@@ -241,6 +305,11 @@ pub fn rook_legal_moves(rooks: u64, friendly_pieces: u64, enemy_pieces: u64) ->
     rook_moves(rooks, friendly_pieces, enemy_pieces)
 }
 
+// Get rook attack targets (squares with enemy pieces that can be captured)
+pub fn rook_attack_targets(rooks: u64, friendly_pieces: u64, enemy_pieces: u64) -> u64 {
+    rook_moves(rooks, friendly_pieces, enemy_pieces) & enemy_pieces
+}
+
 // Queen moves combine bishop and rook moves
 pub fn queen_moves(queens: u64, friendly_pieces: u64, enemy_pieces: u64) -> u64 {
     bishop_moves(queens, friendly_pieces, enemy_pieces) | rook_moves(queens, friendly_pieces, enemy_pieces)
@@ -251,6 +320,11 @@ pub fn queen_legal_moves(queens: u64, friendly_pieces: u64, enemy_pieces: u64) -
     queen_moves(queens, friendly_pieces, enemy_pieces)
 }
 
+// Get queen attack targets (squares with enemy pieces that can be captured)
+pub fn queen_attack_targets(queens: u64, friendly_pieces: u64, enemy_pieces: u64) -> u64 {
+    queen_moves(queens, friendly_pieces, enemy_pieces) & enemy_pieces
+}
+
 // King moves - handling all 8 adjacent squares
 pub fn king_moves(kings: u64) -> u64 {
     let mut moves = 0u64;
@@ -275,6 +349,50 @@ pub fn king_legal_moves(kings: u64, friendly_pieces: u64) -> u64 {
     king_moves(kings) & !friendly_pieces
 }
 
+// Get king attack targets (squares with enemy pieces that can be captured)
+pub fn king_attack_targets(kings: u64, enemy_pieces: u64) -> u64 {
+    king_moves(kings) & enemy_pieces
+}
+
+// Step (file, rank) that leads from one square towards another, if they share a rank, file or diagonal
+fn direction_between(from: u8, to: u8) -> Option<(i8, i8)> {
+    let file_diff = (to % 8) as i8 - (from % 8) as i8;
+    let rank_diff = (to / 8) as i8 - (from / 8) as i8;
+    if from == to || (file_diff != 0 && rank_diff != 0 && file_diff.abs() != rank_diff.abs()) {
+        return None;
+    }
+    Some((file_diff.signum(), rank_diff.signum()))
+}
+
+// Squares strictly between two squares on a shared rank, file or diagonal, empty otherwise
+pub fn squares_between(from: u8, to: u8) -> u64 {
+    let mut squares = 0u64;
+    if let Some((file_step, rank_step)) = direction_between(from, to) {
+        let mut pos = from as i8 + rank_step * 8 + file_step;
+        while pos != to as i8 {
+            squares |= 1u64 << pos;
+            pos += rank_step * 8 + file_step;
+        }
+    }
+    squares
+}
+
+// The whole rank, file or diagonal running through both squares, empty if they are not aligned
+pub fn line_through(a: u8, b: u8) -> u64 {
+    let mut squares = 0u64;
+    if let Some((file_step, rank_step)) = direction_between(a, b) {
+        for (file_step, rank_step) in [(file_step, rank_step), (-file_step, -rank_step)] {
+            let (mut file, mut rank) = ((a % 8) as i8, (a / 8) as i8);
+            while (0..8).contains(&file) && (0..8).contains(&rank) {
+                squares |= 1u64 << (rank * 8 + file);
+                file += file_step;
+                rank += rank_step;
+            }
+        }
+    }
+    squares
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -353,6 +471,18 @@ mod tests {
         assert_eq!(b_pawns_attack_targets(bp, empty_board), 0);
     }
 
+    #[test]
+    fn test_slider_and_king_attack_targets() {
+        let rook = 1u64 << 27;  // d4
+        let friendly = rook | (1u64 << 51);  // own piece on d7
+        let enemy = (1u64 << 31) | (1u64 << 59) | (1u64 << 36);  // h4, d8 behind d7, e5
+        // Only h4 is reachable along a line, d8 is shielded by the friendly d7
+        assert_eq!(rook_attack_targets(rook, friendly, enemy), 1u64 << 31);
+        assert_eq!(bishop_attack_targets(rook, friendly, enemy), 1u64 << 36);
+        assert_eq!(queen_attack_targets(rook, friendly, enemy), (1u64 << 31) | (1u64 << 36));
+        assert_eq!(king_attack_targets(rook, enemy), 1u64 << 36);
+    }
+
     #[test]
     fn test_multiple_pawn_attacks() {
         // Test multiple white pawns attacking
@@ -919,4 +1049,53 @@ mod tests {
         let expected_legal = king_moves(king) & !friendly_pieces;
         assert_eq!(legal_moves, expected_legal);
     }
+
+    #[test]
+    fn test_magic_attacks_match_ray_walking() {
+        use rand::{Rng, SeedableRng};
+
+        // Random occupancies, including sparse and dense boards
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        for _ in 0..200 {
+            let occupied = match rng.gen_range(0..3) {
+                0 => rng.gen::<u64>() & rng.gen::<u64>() & rng.gen::<u64>(),
+                1 => rng.gen::<u64>(),
+                _ => rng.gen::<u64>() | rng.gen::<u64>(),
+            };
+            for square in 0..64u8 {
+                assert_eq!(bishop_attacks(square, occupied), bishop_ray_attacks(square, occupied),
+                    "bishop on {} with occupancy {:#x}", square, occupied);
+                assert_eq!(rook_attacks(square, occupied), rook_ray_attacks(square, occupied),
+                    "rook on {} with occupancy {:#x}", square, occupied);
+            }
+        }
+    }
+
+    #[test]
+    fn test_relevant_occupancy_mask_excludes_edges() {
+        assert_eq!(relevant_occupancy_mask(Square::A1.to_bit_index(), &ROOK_DIRECTIONS).count_ones(), 12);
+        assert_eq!(relevant_occupancy_mask(Square::D4.to_bit_index(), &ROOK_DIRECTIONS).count_ones(), 10);
+        assert_eq!(relevant_occupancy_mask(Square::D4.to_bit_index(), &BISHOP_DIRECTIONS).count_ones(), 9);
+        assert_eq!(relevant_occupancy_mask(Square::A1.to_bit_index(), &BISHOP_DIRECTIONS).count_ones(), 6);
+    }
+
+    #[test]
+    fn test_squares_between() {
+        let e1 = Square::E1.to_bit_index();
+        assert_eq!(squares_between(e1, Square::E4.to_bit_index()), Square::E2.to_bitboard() | Square::E3.to_bitboard());
+        assert_eq!(squares_between(e1, Square::H4.to_bit_index()), Square::F2.to_bitboard() | Square::G3.to_bitboard());
+        assert_eq!(squares_between(Square::H1.to_bit_index(), e1), Square::F1.to_bitboard() | Square::G1.to_bitboard());
+        assert_eq!(squares_between(e1, Square::E2.to_bit_index()), 0);  // adjacent
+        assert_eq!(squares_between(e1, Square::F3.to_bit_index()), 0);  // not aligned
+    }
+
+    #[test]
+    fn test_line_through() {
+        let line = line_through(Square::C3.to_bit_index(), Square::E5.to_bit_index());
+        assert_eq!(line, Square::A1.to_bitboard() | Square::B2.to_bitboard() | Square::C3.to_bitboard() |
+                         Square::D4.to_bitboard() | Square::E5.to_bitboard() | Square::F6.to_bitboard() |
+                         Square::G7.to_bitboard() | Square::H8.to_bitboard());
+        assert_eq!(line_through(Square::A4.to_bit_index(), Square::C4.to_bit_index()), 0x00000000FF000000);
+        assert_eq!(line_through(Square::A1.to_bit_index(), Square::B3.to_bit_index()), 0);
+    }
 }
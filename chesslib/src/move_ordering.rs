@@ -0,0 +1,167 @@
+use crate::board::{Board, Move, PieceType};
+use crate::evaluation::piece_value;
+use crate::search::MAX_PLY;
+
+// Ordering scores, highest first: the hash move, then captures and promotions, then the two
+// killers of the ply, then the remaining quiet moves by history
+const HASH_MOVE_SCORE: i32 = 1_000_000;
+const CAPTURE_SCORE: i32 = 100_000;
+const KILLER_SCORES: [i32; 2] = [90_000, 80_000];
+// History scores are halved once one reaches this, which keeps them below the killers and
+// lets recent cutoffs outweigh old ones
+const HISTORY_LIMIT: i32 = 50_000;
+
+/// Most valuable victim, least valuable attacker: the value taken dominates and the value put
+/// at risk breaks ties. Promotions count the piece gained.
+pub fn mvv_lva(board: &Board, mv: &Move) -> i32 {
+    let victim = if board.is_en_passant(mv) {
+        piece_value(PieceType::Pawn)
+    } else {
+        board.get_piece_at_square(mv.target.to_bit_index()).map_or(0, |piece| piece_value(piece.piece_type()))
+    };
+    let attacker = board.get_piece_at_square(mv.src.to_bit_index()).map_or(0, |piece| piece_value(piece.piece_type()));
+    let promotion = mv.promotion.map_or(0, piece_value);
+    (victim + promotion) * 10 - attacker
+}
+
+/// Puts the moves most likely to cause a cutoff first, learning from the cutoffs of the
+/// current search
+///
+/// Killer moves are quiet moves that refuted another move at the same ply, and are often
+/// just as good against its siblings. The history heuristic remembers which quiet moves
+/// caused cutoffs anywhere in the tree, weighted by the depth of the search they cut.
+#[derive(Debug)]
+pub struct MoveOrdering {
+    killers: Vec<[Option<Move>; 2]>,
+    // Indexed by source and target square
+    history: [[i32; 64]; 64],
+}
+
+impl Default for MoveOrdering {
+    fn default() -> MoveOrdering {
+        MoveOrdering {
+            killers: vec![[None; 2]; MAX_PLY as usize + 1],
+            history: [[0; 64]; 64],
+        }
+    }
+}
+
+impl MoveOrdering {
+    pub fn new() -> MoveOrdering {
+        MoveOrdering::default()
+    }
+
+    /// Prepares for a new search: killers only make sense within one tree, while history is
+    /// aged so it still helps without drowning out what the new search finds
+    pub fn new_search(&mut self) {
+        self.killers.iter_mut().for_each(|killers| *killers = [None; 2]);
+        self.age_history();
+    }
+
+    /// How promising `mv` looks at `ply`, higher is better
+    pub fn score(&self, board: &Board, mv: &Move, hash_move: Option<Move>, ply: usize) -> i32 {
+        if Some(*mv) == hash_move {
+            HASH_MOVE_SCORE
+        } else if board.is_capture(mv) || mv.promotion.is_some() {
+            CAPTURE_SCORE + mvv_lva(board, mv)
+        } else if let Some(slot) = self.killers[ply].iter().position(|killer| *killer == Some(*mv)) {
+            KILLER_SCORES[slot]
+        } else {
+            self.history[mv.src.to_bit_index() as usize][mv.target.to_bit_index() as usize]
+        }
+    }
+
+    /// Sorts the moves from most to least promising
+    pub fn order(&self, board: &Board, moves: &mut [Move], hash_move: Option<Move>, ply: usize) {
+        moves.sort_by_cached_key(|mv| -self.score(board, mv, hash_move, ply));
+    }
+
+    /// Remembers a quiet move that caused a beta cutoff at `ply` with `depth` left to search.
+    /// Captures and promotions are ordered well already and are not recorded.
+    pub fn record_cutoff(&mut self, board: &Board, mv: &Move, ply: usize, depth: u32) {
+        if board.is_capture(mv) || mv.promotion.is_some() {
+            return;
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(*mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(*mv);
+        }
+
+        let entry = &mut self.history[mv.src.to_bit_index() as usize][mv.target.to_bit_index() as usize];
+        *entry += (depth * depth) as i32;
+        if *entry >= HISTORY_LIMIT {
+            self.age_history();
+        }
+    }
+
+    fn age_history(&mut self) {
+        self.history.iter_mut().flatten().for_each(|score| *score /= 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::PAWN_VALUE;
+
+    fn mv(s: &str) -> Move {
+        Move::try_from(s).unwrap()
+    }
+
+    #[test]
+    fn test_mvv_lva() {
+        let board = Board::from_fen("4k3/8/8/3q4/2P5/4N3/8/4K3 w - - 0 1").unwrap();
+        assert!(mvv_lva(&board, &mv("c4d5")) > mvv_lva(&board, &mv("e3d5")));
+        assert!(mvv_lva(&board, &mv("e3d5")) > mvv_lva(&board, &mv("e3g4")));
+
+        let en_passant = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        assert_eq!(mvv_lva(&en_passant, &mv("e5d6")), PAWN_VALUE * 10 - PAWN_VALUE);
+    }
+
+    #[test]
+    fn test_score_ranks_move_kinds() {
+        let board = Board::from_fen("4k3/8/8/3q4/2P5/4N3/8/4K3 w - - 0 1").unwrap();
+        let mut ordering = MoveOrdering::new();
+        ordering.record_cutoff(&board, &mv("e1f2"), 3, 4);
+        ordering.record_cutoff(&board, &mv("e3g4"), 3, 4);
+        ordering.record_cutoff(&board, &mv("e1d1"), 5, 6);
+
+        let hash = ordering.score(&board, &mv("e3f5"), Some(mv("e3f5")), 3);
+        let capture = ordering.score(&board, &mv("c4d5"), Some(mv("e3f5")), 3);
+        let first_killer = ordering.score(&board, &mv("e3g4"), None, 3);
+        let second_killer = ordering.score(&board, &mv("e1f2"), None, 3);
+        let history = ordering.score(&board, &mv("e1d1"), None, 3);
+        let quiet = ordering.score(&board, &mv("e1e2"), None, 3);
+        assert!(hash > capture && capture > first_killer && first_killer > second_killer);
+        assert!(second_killer > history && history > quiet);
+        assert_eq!(quiet, 0);
+    }
+
+    #[test]
+    fn test_record_cutoff_ignores_captures_and_duplicates() {
+        let board = Board::from_fen("4k3/8/8/3q4/2P5/4N3/8/4K3 w - - 0 1").unwrap();
+        let mut ordering = MoveOrdering::new();
+        ordering.record_cutoff(&board, &mv("c4d5"), 2, 3);
+        assert_eq!(ordering.killers[2], [None, None]);
+
+        ordering.record_cutoff(&board, &mv("e3g4"), 2, 3);
+        ordering.record_cutoff(&board, &mv("e3g4"), 2, 3);
+        assert_eq!(ordering.killers[2], [Some(mv("e3g4")), None]);
+        assert_eq!(ordering.history[20][30], 18);
+
+        ordering.new_search();
+        assert_eq!(ordering.killers[2], [None, None]);
+        assert_eq!(ordering.history[20][30], 9);
+    }
+
+    #[test]
+    fn test_order() {
+        let board = Board::from_fen("4k3/8/8/3q4/2P5/4N3/8/4K3 w - - 0 1").unwrap();
+        let mut moves = board.legal_moves();
+        MoveOrdering::new().order(&board, &mut moves, Some(mv("e1f1")), 0);
+        assert_eq!(moves[0], mv("e1f1"));
+        assert_eq!(moves[1], mv("c4d5"));
+        assert_eq!(moves[2], mv("e3d5"));
+    }
+}
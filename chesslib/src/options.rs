@@ -0,0 +1,248 @@
+use crate::time_manager::{DEFAULT_MOVE_OVERHEAD_MS, MAX_MOVE_OVERHEAD_MS, MIN_MOVE_OVERHEAD_MS};
+use crate::transposition::{DEFAULT_HASH_SIZE_MB, MAX_HASH_SIZE_MB, MIN_HASH_SIZE_MB};
+
+/// Names of the options the engine advertises
+pub const HASH: &str = "Hash";
+pub const CLEAR_HASH: &str = "Clear Hash";
+pub const MOVE_OVERHEAD: &str = "Move Overhead";
+pub const DEBUG_LOG_FILE: &str = "Debug Log File";
+
+/// Type of a UCI option, with its default and the values it accepts
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionType {
+    /// A whole number between `min` and `max`
+    Spin { default: i64, min: i64, max: i64 },
+    /// `true` or `false`
+    Check { default: bool },
+    /// One of a fixed list of strings
+    Combo { default: &'static str, choices: &'static [&'static str] },
+    /// Any string, `<empty>` standing for the empty one
+    String { default: &'static str },
+    /// An action without a value
+    Button,
+}
+
+/// Current value of an option; buttons have none
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionValue {
+    Spin(i64),
+    Check(bool),
+    String(String),
+    Button,
+}
+
+/// An option the GUI can see and set
+#[derive(Debug, Clone, PartialEq)]
+pub struct UciOption {
+    pub name: &'static str,
+    pub option_type: OptionType,
+    pub value: OptionValue,
+}
+
+impl UciOption {
+    /// The `option` line advertising this option in the reply to `uci`
+    pub fn uci_line(&self) -> String {
+        let details = match &self.option_type {
+            OptionType::Spin { default, min, max } => format!("spin default {} min {} max {}", default, min, max),
+            OptionType::Check { default } => format!("check default {}", default),
+            OptionType::Combo { default, choices } => {
+                let vars: Vec<String> = choices.iter().map(|choice| format!("var {}", choice)).collect();
+                format!("combo default {} {}", default, vars.join(" "))
+            },
+            OptionType::String { default } => format!("string default {}", if default.is_empty() { "<empty>" } else { default }),
+            OptionType::Button => "button".to_string(),
+        };
+        format!("option name {} type {}", self.name, details)
+    }
+
+    // Checks `value` against the option's type, and turns it into the value to store
+    fn parse(&self, value: Option<&str>) -> Result<OptionValue, String> {
+        match &self.option_type {
+            OptionType::Spin { min, max, .. } => match value.and_then(|value| value.parse::<i64>().ok()) {
+                Some(value) if (*min..=*max).contains(&value) => Ok(OptionValue::Spin(value)),
+                _ => Err(format!("{} must be between {} and {}", self.name, min, max)),
+            },
+            OptionType::Check { .. } => match value.map(|value| value.to_lowercase()).as_deref() {
+                Some("true") => Ok(OptionValue::Check(true)),
+                Some("false") => Ok(OptionValue::Check(false)),
+                _ => Err(format!("{} must be true or false", self.name)),
+            },
+            OptionType::Combo { choices, .. } => {
+                match choices.iter().find(|choice| value.is_some_and(|value| choice.eq_ignore_ascii_case(value))) {
+                    Some(choice) => Ok(OptionValue::String(choice.to_string())),
+                    None => Err(format!("{} must be one of {}", self.name, choices.join(", "))),
+                }
+            },
+            OptionType::String { .. } => match value {
+                None | Some("<empty>") => Ok(OptionValue::String(String::new())),
+                Some(value) => Ok(OptionValue::String(value.to_string())),
+            },
+            OptionType::Button => Ok(OptionValue::Button),
+        }
+    }
+}
+
+/// Registry of UCI options: what the engine advertises on `uci`, checks on `setoption`, and
+/// reads back while it plays
+///
+/// Names are matched case-insensitively, as GUIs do not agree on capitalization.
+///
+/// # Examples
+///
+/// ```
+/// use chesslib::options::{engine_options, HASH};
+/// let mut options = engine_options();
+/// assert_eq!(options.set("hash", Some("64")), Ok(HASH));
+/// assert_eq!(options.spin(HASH), Some(64));
+/// assert!(options.set("Hash", Some("0")).is_err());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Options {
+    options: Vec<UciOption>,
+}
+
+impl Options {
+    /// Creates a registry without any options
+    pub fn new() -> Options {
+        Options::default()
+    }
+
+    /// Adds an option, set to its default
+    pub fn register(&mut self, name: &'static str, option_type: OptionType) {
+        let value = match &option_type {
+            OptionType::Spin { default, .. } => OptionValue::Spin(*default),
+            OptionType::Check { default } => OptionValue::Check(*default),
+            OptionType::Combo { default, .. } | OptionType::String { default } => OptionValue::String(default.to_string()),
+            OptionType::Button => OptionValue::Button,
+        };
+        self.options.push(UciOption { name, option_type, value });
+    }
+
+    /// The options in the order they were registered
+    pub fn iter(&self) -> impl Iterator<Item = &UciOption> {
+        self.options.iter()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&UciOption> {
+        self.options.iter().find(|option| option.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Sets an option from the name and value of a `setoption` command, returning the name as
+    /// registered so the caller can act on the change
+    ///
+    /// An invalid value leaves the option as it was.
+    pub fn set(&mut self, name: &str, value: Option<&str>) -> Result<&'static str, String> {
+        let option = self.options.iter_mut()
+            .find(|option| option.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("unknown option: {}", name))?;
+        option.value = option.parse(value)?;
+        Ok(option.name)
+    }
+
+    /// Value of a spin option, `None` if there is no such option or it is of another type
+    pub fn spin(&self, name: &str) -> Option<i64> {
+        match self.get(name)?.value {
+            OptionValue::Spin(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Value of a check option
+    pub fn check(&self, name: &str) -> Option<bool> {
+        match self.get(name)?.value {
+            OptionValue::Check(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Value of a combo or string option
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match &self.get(name)?.value {
+            OptionValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// The options this engine offers, at their defaults
+pub fn engine_options() -> Options {
+    let mut options = Options::new();
+    options.register(HASH, OptionType::Spin {
+        default: DEFAULT_HASH_SIZE_MB as i64,
+        min: MIN_HASH_SIZE_MB as i64,
+        max: MAX_HASH_SIZE_MB as i64,
+    });
+    options.register(CLEAR_HASH, OptionType::Button);
+    options.register(MOVE_OVERHEAD, OptionType::Spin {
+        default: DEFAULT_MOVE_OVERHEAD_MS as i64,
+        min: MIN_MOVE_OVERHEAD_MS as i64,
+        max: MAX_MOVE_OVERHEAD_MS as i64,
+    });
+    options.register(DEBUG_LOG_FILE, OptionType::String { default: "" });
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_types() -> Options {
+        let mut options = Options::new();
+        options.register("Threads", OptionType::Spin { default: 1, min: 1, max: 8 });
+        options.register("Ponder", OptionType::Check { default: false });
+        options.register("Style", OptionType::Combo { default: "Normal", choices: &["Solid", "Normal", "Risky"] });
+        options.register("Book File", OptionType::String { default: "" });
+        options.register("Clear Hash", OptionType::Button);
+        options
+    }
+
+    #[test]
+    fn test_uci_lines() {
+        let lines: Vec<String> = all_types().iter().map(UciOption::uci_line).collect();
+        assert_eq!(lines, vec![
+            "option name Threads type spin default 1 min 1 max 8",
+            "option name Ponder type check default false",
+            "option name Style type combo default Normal var Solid var Normal var Risky",
+            "option name Book File type string default <empty>",
+            "option name Clear Hash type button",
+        ]);
+    }
+
+    #[test]
+    fn test_set_and_query() {
+        let mut options = all_types();
+        assert_eq!(options.set("threads", Some("4")), Ok("Threads"));
+        assert_eq!(options.spin("Threads"), Some(4));
+        assert_eq!(options.set("Ponder", Some("TRUE")), Ok("Ponder"));
+        assert_eq!(options.check("ponder"), Some(true));
+        assert_eq!(options.set("Style", Some("risky")), Ok("Style"));
+        assert_eq!(options.string("Style"), Some("Risky"));
+        assert_eq!(options.set("Book File", Some("/tmp/my book.bin")), Ok("Book File"));
+        assert_eq!(options.string("Book File"), Some("/tmp/my book.bin"));
+        assert_eq!(options.set("Book File", Some("<empty>")), Ok("Book File"));
+        assert_eq!(options.string("Book File"), Some(""));
+        assert_eq!(options.set("clear hash", None), Ok("Clear Hash"));
+        // Asking for the wrong type finds nothing
+        assert_eq!(options.check("Threads"), None);
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        let mut options = all_types();
+        assert_eq!(options.set("Threads", Some("9")), Err("Threads must be between 1 and 8".to_string()));
+        assert_eq!(options.set("Threads", None), Err("Threads must be between 1 and 8".to_string()));
+        assert_eq!(options.set("Ponder", Some("yes")), Err("Ponder must be true or false".to_string()));
+        assert_eq!(options.set("Style", Some("Wild")), Err("Style must be one of Solid, Normal, Risky".to_string()));
+        assert_eq!(options.set("Contempt", Some("3")), Err("unknown option: Contempt".to_string()));
+        assert_eq!(options, all_types());
+    }
+
+    #[test]
+    fn test_engine_options() {
+        let options = engine_options();
+        assert_eq!(options.spin(HASH), Some(DEFAULT_HASH_SIZE_MB as i64));
+        assert_eq!(options.spin(MOVE_OVERHEAD), Some(DEFAULT_MOVE_OVERHEAD_MS as i64));
+        assert_eq!(options.get(CLEAR_HASH).map(|option| &option.option_type), Some(&OptionType::Button));
+        assert_eq!(options.string(DEBUG_LOG_FILE), Some(""));
+    }
+}
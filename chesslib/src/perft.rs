@@ -0,0 +1,174 @@
+use crate::board::{Board, Move};
+
+/// Leaf node counts of a perft run, broken down by the kind of move that reached the leaf
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct PerftCounts {
+    pub nodes: u64,
+    pub captures: u64,
+    pub en_passants: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    pub checks: u64,
+    pub checkmates: u64,
+}
+
+impl PerftCounts {
+    /// Counts the leaf reached by playing `mv` on `board`, which is left unchanged
+    fn record_leaf(&mut self, board: &mut Board, mv: &Move) {
+        self.nodes += 1;
+        if board.is_capture(mv) {
+            self.captures += 1;
+        }
+        if board.is_en_passant(mv) {
+            self.en_passants += 1;
+        }
+        if board.is_castling(mv) {
+            self.castles += 1;
+        }
+        if mv.promotion.is_some() {
+            self.promotions += 1;
+        }
+        let undo = board.make_move(mv);
+        if board.is_check() {
+            self.checks += 1;
+            if board.legal_moves().is_empty() {
+                self.checkmates += 1;
+            }
+        }
+        board.unmake_move(undo);
+    }
+}
+
+impl std::ops::AddAssign for PerftCounts {
+    fn add_assign(&mut self, other: PerftCounts) {
+        self.nodes += other.nodes;
+        self.captures += other.captures;
+        self.en_passants += other.en_passants;
+        self.castles += other.castles;
+        self.promotions += other.promotions;
+        self.checks += other.checks;
+        self.checkmates += other.checkmates;
+    }
+}
+
+impl Board {
+    /// Counts the leaf nodes of the legal move tree `depth` plies deep
+    ///
+    /// Comparing against published counts for well-known positions is the standard way to
+    /// check a move generator, since any missing or extra move changes the total.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chesslib::board::get_starting_board;
+    /// assert_eq!(get_starting_board().perft(3), 8902);
+    /// ```
+    pub fn perft(&self, depth: u32) -> u64 {
+        self.clone().perft_in_place(depth)
+    }
+
+    // Walks the tree with make/unmake on a single board rather than cloning per node
+    fn perft_in_place(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves();
+        if depth == 1 {
+            // Every legal move is a leaf, no need to play them
+            return moves.len() as u64;
+        }
+        moves.iter()
+            .map(|mv| {
+                let undo = self.make_move(mv);
+                let nodes = self.perft_in_place(depth - 1);
+                self.unmake_move(undo);
+                nodes
+            })
+            .sum()
+    }
+
+    /// Like `perft`, but also classifies the moves leading to the leaves
+    pub fn perft_counts(&self, depth: u32) -> PerftCounts {
+        if depth == 0 {
+            return PerftCounts { nodes: 1, ..PerftCounts::default() };
+        }
+        let mut counts = PerftCounts::default();
+        self.clone().accumulate_perft_counts(depth, &mut counts);
+        counts
+    }
+
+    fn accumulate_perft_counts(&mut self, depth: u32, counts: &mut PerftCounts) {
+        for mv in self.legal_moves() {
+            if depth == 1 {
+                counts.record_leaf(self, &mv);
+            } else {
+                let undo = self.make_move(&mv);
+                self.accumulate_perft_counts(depth - 1, counts);
+                self.unmake_move(undo);
+            }
+        }
+    }
+
+    /// Splits the perft count by root move, which narrows a wrong total down to the moves
+    /// whose subtree differs from a reference engine's
+    pub fn divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        self.divide_counts(depth)
+            .into_iter()
+            .map(|(mv, counts)| (mv, counts.nodes))
+            .collect()
+    }
+
+    /// Like `divide`, with the full breakdown for every root move
+    pub fn divide_counts(&self, depth: u32) -> Vec<(Move, PerftCounts)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        let mut board = self.clone();
+        self.legal_moves()
+            .into_iter()
+            .map(|mv| {
+                let mut counts = PerftCounts::default();
+                if depth == 1 {
+                    counts.record_leaf(&mut board, &mv);
+                } else {
+                    let undo = board.make_move(&mv);
+                    board.accumulate_perft_counts(depth - 1, &mut counts);
+                    board.unmake_move(undo);
+                }
+                (mv, counts)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::get_starting_board;
+
+    #[test]
+    fn test_perft_depth_zero_is_one_node() {
+        assert_eq!(get_starting_board().perft(0), 1);
+        assert_eq!(get_starting_board().perft_counts(0).nodes, 1);
+        assert!(get_starting_board().divide(0).is_empty());
+    }
+
+    #[test]
+    fn test_divide_sums_to_perft() {
+        let board = get_starting_board();
+        let divided = board.divide(3);
+        assert_eq!(divided.len(), 20);
+        assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), board.perft(3));
+
+        let e2e4 = divided.iter().find(|(mv, _)| mv.to_string() == "e2e4").unwrap();
+        assert_eq!(e2e4.1, 600);
+    }
+
+    #[test]
+    fn test_perft_counts_match_perft() {
+        let board = get_starting_board();
+        let counts = board.perft_counts(3);
+        assert_eq!(counts.nodes, board.perft(3));
+        assert_eq!(counts, PerftCounts { nodes: 8902, captures: 34, checks: 12, ..PerftCounts::default() });
+    }
+}
@@ -0,0 +1,677 @@
+use crate::board::{Board, Move};
+use crate::evaluation::evaluate;
+use crate::move_ordering::MoveOrdering;
+use crate::time_manager::TimeBudget;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::transposition::{Bound, TranspositionTable};
+
+/// Score of being checkmated on the spot; mates further away score closer to zero
+pub const MATE_SCORE: i32 = 30_000;
+/// Bound outside any score the search returns
+pub const INFINITY: i32 = 32_000;
+/// Deepest ply the search can reach, which keeps mate scores apart from ordinary ones
+pub const MAX_PLY: i32 = 128;
+
+/// Depth `go` searches to when the GUI does not ask for one
+pub const DEFAULT_DEPTH: u32 = 4;
+
+// The clock is only looked at every this many nodes, as reading it is slow
+const NODES_BETWEEN_TIME_CHECKS: u64 = 1024;
+
+// How often a long iteration reports progress, and how long the search runs before it starts
+// announcing each root move
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+// Iterations from this depth on start with a window this wide around the previous score, which
+// widens fourfold each time the score falls outside it
+const ASPIRATION_MIN_DEPTH: u32 = 4;
+const ASPIRATION_WINDOW: i32 = 50;
+
+/// Result of one completed iteration of iterative deepening
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub depth: u32,
+    /// Deepest ply reached in the iteration, counting the quiescence search
+    pub seldepth: u32,
+    /// Score in centipawns from the side to move's point of view, or a mate score
+    pub score: i32,
+    /// `Exact` for a completed iteration; `Lower` or `Upper` when the score fell outside the
+    /// aspiration window and is only a bound
+    pub bound: Bound,
+    /// Principal variation, the line both sides are expected to play
+    pub pv: Vec<Move>,
+    /// Positions visited so far, over all iterations
+    pub nodes: u64,
+    /// Time since the search started
+    pub time: Duration,
+    /// How full the transposition table is, in permille
+    pub hashfull: u32,
+}
+
+impl SearchResult {
+    /// First move of the principal variation, `None` if the game is already over
+    pub fn best_move(&self) -> Option<Move> {
+        self.pv.first().copied()
+    }
+}
+
+/// What the search reports while it runs
+#[derive(Debug, Clone, Copy)]
+pub enum SearchInfo<'a> {
+    /// An iteration completed, or its score fell outside the aspiration window and it is about
+    /// to be searched again, as `result.bound` tells
+    Iteration(&'a SearchResult),
+    /// The root move about to be searched, numbered from 1 in the order they are tried; only
+    /// sent once the search has run for a while
+    CurrentMove { depth: u32, mv: Move, number: usize },
+    /// Sent about once a second, so a long iteration still shows signs of life
+    Progress { nodes: u64, time: Duration, hashfull: u32 },
+}
+
+/// Number of moves to a forced mate if `score` is a mate score: positive when the side to
+/// move mates, negative when it gets mated
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score.abs() < MATE_SCORE - MAX_PLY {
+        return None;
+    }
+    let plies = MATE_SCORE - score.abs();
+    let moves = (plies + 1) / 2;
+    Some(if score > 0 { moves } else { -moves })
+}
+
+// Mate scores count plies from the root, but a table entry can be reached at any ply, so they
+// are stored relative to the position itself
+fn score_to_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_SCORE - MAX_PLY {
+        score + ply
+    } else if score <= -MATE_SCORE + MAX_PLY {
+        score - ply
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_SCORE - MAX_PLY {
+        score - ply
+    } else if score <= -MATE_SCORE + MAX_PLY {
+        score + ply
+    } else {
+        score
+    }
+}
+
+/// Negamax alpha-beta search driven by iterative deepening
+#[derive(Debug, Default)]
+pub struct Searcher {
+    nodes: u64,
+    // Principal variation of the last completed iteration, tried first in the next one
+    previous_pv: Vec<Move>,
+    tt: TranspositionTable,
+    ordering: MoveOrdering,
+    // Only try the hash move first, only useful to measure what ordering gains
+    unordered: bool,
+    // Point at which the current iteration is abandoned, if the search is timed
+    deadline: Option<Instant>,
+    // Raised from another thread to end the search early
+    stop: Arc<AtomicBool>,
+    // Whether a deadline or stop request may cut the current iteration short
+    interruptible: bool,
+    aborted: bool,
+    start: Option<Instant>,
+    // Deepest ply reached in the current iteration
+    seldepth: i32,
+    next_progress: Option<Instant>,
+    progress_due: bool,
+}
+
+impl Searcher {
+    pub fn new() -> Searcher {
+        Searcher::default()
+    }
+
+    /// Creates a searcher whose transposition table uses at most `size_mb` megabytes
+    pub fn with_hash_size(size_mb: usize) -> Searcher {
+        Searcher { tt: TranspositionTable::new(size_mb), ..Searcher::default() }
+    }
+
+    /// Resizes the transposition table, forgetting everything in it
+    pub fn set_hash_size(&mut self, size_mb: usize) {
+        self.tt.resize(size_mb);
+    }
+
+    /// Forgets every position searched so far and what was learned about move ordering, as
+    /// at the start of a new game
+    pub fn clear_hash(&mut self) {
+        self.tt.clear();
+        self.ordering = MoveOrdering::new();
+    }
+
+    /// Shares a flag another thread can raise to end the search as if time had run out
+    ///
+    /// The flag is never lowered by the searcher, so a stop requested just before the search
+    /// starts is not lost; it is up to the owner to lower it before the next search.
+    pub fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = stop;
+    }
+
+    /// Turns move ordering on or off; it is on by default, and turning it off only serves to
+    /// measure how many nodes it saves
+    ///
+    /// With ordering off the main search still tries the hash move first, and the quiescence
+    /// search still orders its captures, since without that it hardly terminates.
+    pub fn set_move_ordering(&mut self, enabled: bool) {
+        self.unordered = !enabled;
+    }
+
+    /// Searches `board` one ply deeper at a time up to `max_depth`, telling `report` about each
+    /// completed iteration and about progress in between, and returns the result of the
+    /// deepest one
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chesslib::board::Board;
+    /// use chesslib::search::{mate_in, Searcher};
+    /// let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    ///
+    /// let result = Searcher::new().search(&board, 2, |_| {});
+    /// assert_eq!(result.best_move().unwrap().to_string(), "a1a8");
+    /// assert_eq!(mate_in(result.score), Some(1));
+    /// ```
+    pub fn search(&mut self, board: &Board, max_depth: u32, report: impl FnMut(SearchInfo)) -> SearchResult {
+        self.search_with_budget(board, max_depth, None, report)
+    }
+
+    /// Like `search`, but also stops once the time budget runs out
+    ///
+    /// No iteration is started after the soft limit. An iteration still running at the hard
+    /// limit, or when the stop flag is raised, is abandoned and the result of the previous one
+    /// returned, except for the first iteration, which always completes so there is a move to
+    /// play.
+    pub fn search_with_budget(&mut self, board: &Board, max_depth: u32, budget: Option<TimeBudget>,
+                              mut report: impl FnMut(SearchInfo)) -> SearchResult {
+        let start = Instant::now();
+        self.start = Some(start);
+        self.next_progress = Some(start + PROGRESS_INTERVAL);
+        self.progress_due = false;
+        self.nodes = 0;
+        self.previous_pv.clear();
+        self.aborted = false;
+        self.interruptible = false;
+        self.deadline = None;
+        self.tt.new_search();
+        self.ordering.new_search();
+        let mut board = board.clone();
+        let mut result = self.result(0, 0, Bound::Exact, Vec::new());
+
+        'deepening: for depth in 1..=max_depth.max(1) {
+            self.seldepth = 0;
+            // The score most likely lands close to the last one, and a narrow window is
+            // searched faster
+            let mut delta = ASPIRATION_WINDOW;
+            let (mut alpha, mut beta) = if depth >= ASPIRATION_MIN_DEPTH && mate_in(result.score).is_none() {
+                (result.score - delta, result.score + delta)
+            } else {
+                (-INFINITY, INFINITY)
+            };
+            let (score, pv) = loop {
+                let mut pv = Vec::new();
+                let score = self.negamax(&mut board, depth, 0, alpha, beta, &mut pv, &mut report);
+                if self.aborted {
+                    break 'deepening;
+                }
+                let bound = if score <= alpha {
+                    Bound::Upper
+                } else if score >= beta {
+                    Bound::Lower
+                } else {
+                    break (score, pv);
+                };
+                report(SearchInfo::Iteration(&self.result(depth, score, bound, pv)));
+                delta *= 4;
+                if bound == Bound::Upper {
+                    alpha = (score - delta).max(-INFINITY);
+                } else {
+                    beta = (score + delta).min(INFINITY);
+                }
+            };
+            result = self.result(depth, score, Bound::Exact, pv);
+            report(SearchInfo::Iteration(&result));
+            self.previous_pv = result.pv.clone();
+
+            // Nothing to look at, or a mate already found cannot get any shorter
+            if result.pv.is_empty() || mate_in(score).is_some() {
+                break;
+            }
+            if self.stop.load(Ordering::Relaxed) || budget.is_some_and(|budget| start.elapsed() >= budget.soft) {
+                break;
+            }
+            self.deadline = budget.map(|budget| start + budget.hard);
+            self.interruptible = true;
+        }
+        result
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.start.map_or(Duration::ZERO, |start| start.elapsed())
+    }
+
+    fn result(&self, depth: u32, score: i32, bound: Bound, pv: Vec<Move>) -> SearchResult {
+        SearchResult {
+            depth,
+            seldepth: self.seldepth as u32,
+            score,
+            bound,
+            pv,
+            nodes: self.nodes,
+            time: self.elapsed(),
+            hashfull: self.tt.hashfull(),
+        }
+    }
+
+    // Checks for a stop request, and the clock now and then, remembering once it is time to
+    // stop so the whole tree unwinds without another look
+    fn should_stop(&mut self) -> bool {
+        if self.aborted {
+            return true;
+        }
+        if self.nodes.is_multiple_of(NODES_BETWEEN_TIME_CHECKS) {
+            let now = Instant::now();
+            self.progress_due |= self.next_progress.is_some_and(|next_progress| now >= next_progress);
+            self.aborted = self.interruptible && self.deadline.is_some_and(|deadline| now >= deadline);
+        }
+        if self.interruptible && self.stop.load(Ordering::Relaxed) {
+            self.aborted = true;
+        }
+        self.aborted
+    }
+
+    fn report_progress(&mut self, report: &mut dyn FnMut(SearchInfo)) {
+        self.progress_due = false;
+        self.next_progress = Some(Instant::now() + PROGRESS_INTERVAL);
+        report(SearchInfo::Progress { nodes: self.nodes, time: self.elapsed(), hashfull: self.tt.hashfull() });
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn negamax(&mut self, board: &mut Board, depth: u32, ply: i32, mut alpha: i32, beta: i32, pv: &mut Vec<Move>,
+               report: &mut dyn FnMut(SearchInfo)) -> i32 {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+        pv.clear();
+        if self.should_stop() {
+            return 0;
+        }
+        if self.progress_due {
+            self.report_progress(report);
+        }
+
+        // Going back to a position already seen is a draw, since if it was worth repeating
+        // once it can be repeated again
+        if ply > 0 && board.is_repetition(2) {
+            return 0;
+        }
+
+        let mut moves = board.legal_moves();
+        if moves.is_empty() {
+            // Checkmated positions score worse the sooner they happen
+            return if board.is_check() { -MATE_SCORE + ply } else { 0 };
+        }
+        // Checkmate is the one thing that beats the fifty-move rule
+        if ply > 0 && board.is_fifty_move_draw() {
+            return 0;
+        }
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiescence(board, ply, true, alpha, beta);
+        }
+
+        // A deep enough search of this position may already settle it. The root is always
+        // searched so there is a move to play.
+        let tt_entry = self.tt.probe(board.hash);
+        let tt_move = tt_entry.and_then(|entry| entry.best_move).filter(|mv| moves.contains(mv));
+        if let Some(entry) = tt_entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            let score = score_from_tt(entry.score, ply);
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if cutoff {
+                pv.extend(tt_move);
+                return score;
+            }
+        }
+
+        // The move found best last time, here or in the last iteration's best line, is the
+        // most likely to be best again
+        let hash_move = tt_move.or_else(|| self.previous_pv.get(ply as usize).copied());
+        if !self.unordered {
+            self.ordering.order(board, &mut moves, hash_move, ply as usize);
+        } else if let Some(index) = hash_move.and_then(|hash_move| moves.iter().position(|mv| *mv == hash_move)) {
+            moves.swap(0, index);
+        }
+
+        let original_alpha = alpha;
+        let mut best_move = None;
+        let mut child_pv = Vec::new();
+        for (index, mv) in moves.into_iter().enumerate() {
+            if ply == 0 && self.elapsed() >= PROGRESS_INTERVAL {
+                report(SearchInfo::CurrentMove { depth, mv, number: index + 1 });
+            }
+            let undo = board.make_move(&mv);
+            let score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, &mut child_pv, report);
+            board.unmake_move(undo);
+            // An abandoned subtree's score means nothing, and must not reach the table
+            if self.aborted {
+                return 0;
+            }
+
+            if score > alpha {
+                alpha = score;
+                best_move = Some(mv);
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&child_pv);
+                if alpha >= beta {
+                    self.ordering.record_cutoff(board, &mv, ply as usize, depth);
+                    break;
+                }
+            }
+        }
+
+        let bound = if alpha >= beta {
+            Bound::Lower
+        } else if alpha > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(board.hash, depth, bound, score_to_tt(alpha, ply), best_move.or(tt_move));
+        alpha
+    }
+
+    // Searches captures and promotions only until the position is quiet, so the search never
+    // stops in the middle of an exchange. The side to move may also "stand pat" on the static
+    // evaluation rather than capture. A check given by the last move of the main search is
+    // answered with every evasion so mates on the horizon are seen; trying all evasions
+    // further down makes long checking sequences explode, so there only captures are tried.
+    // Standing pat while in check is still allowed there, even though it can score a position
+    // the side to move could not legally stay in.
+    fn quiescence(&mut self, board: &mut Board, ply: i32, evade_checks: bool, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+        if self.should_stop() {
+            return 0;
+        }
+
+        let mut moves = if evade_checks && board.is_check() {
+            let evasions = board.legal_moves();
+            if evasions.is_empty() {
+                return -MATE_SCORE + ply;
+            }
+            evasions
+        } else {
+            let stand_pat = evaluate(board);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            board.legal_captures()
+        };
+        if ply >= MAX_PLY {
+            return evaluate(board);
+        }
+
+        // Winning the most valuable piece with the least valuable one first makes cutoffs come
+        // early, which keeps the capture sequences from blowing up
+        self.ordering.order(board, &mut moves, None, ply as usize);
+
+        for mv in moves {
+            let undo = board.make_move(&mv);
+            let score = -self.quiescence(board, ply + 1, false, -beta, -alpha);
+            board.unmake_move(undo);
+            if self.aborted {
+                return 0;
+            }
+
+            if score > alpha {
+                alpha = score;
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        alpha
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::get_starting_board;
+    use std::time::Duration;
+
+    fn search(fen: &str, depth: u32) -> SearchResult {
+        Searcher::new().search(&Board::from_fen(fen).unwrap(), depth, |_| {})
+    }
+
+    // Depth of a completed iteration, leaving out aspiration failures and progress reports
+    fn completed_depth(info: SearchInfo) -> Option<u32> {
+        match info {
+            SearchInfo::Iteration(result) if result.bound == Bound::Exact => Some(result.depth),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_mate_in() {
+        assert_eq!(mate_in(MATE_SCORE - 1), Some(1));
+        assert_eq!(mate_in(MATE_SCORE - 3), Some(2));
+        assert_eq!(mate_in(-MATE_SCORE + 2), Some(-1));
+        assert_eq!(mate_in(250), None);
+        assert_eq!(mate_in(-900), None);
+    }
+
+    #[test]
+    fn test_finds_mate_in_two() {
+        // For example 1. Ke6 Ke8 2. Qg8#
+        let result = search("5k2/8/5K2/8/8/8/8/6Q1 w - - 0 1", 4);
+        assert_eq!(mate_in(result.score), Some(2));
+        assert_eq!(result.pv.len(), 3);
+    }
+
+    #[test]
+    fn test_sees_it_gets_mated() {
+        // Ke8 is forced, then Qe7 mates
+        let result = search("5k2/7Q/5K2/8/8/8/8/8 b - - 0 1", 2);
+        assert_eq!(mate_in(result.score), Some(-1), "pv {:?}", result.pv);
+    }
+
+    #[test]
+    fn test_wins_hanging_queen() {
+        let result = search("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", 2);
+        assert_eq!(result.best_move().unwrap().to_string(), "d1d5");
+        assert!(result.score > 400);
+    }
+
+    #[test]
+    fn test_avoids_losing_the_queen() {
+        // Taking the pawn on d5 loses the queen to the e6 pawn
+        let result = search("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", 3);
+        assert_ne!(result.best_move().unwrap().to_string(), "d1d5");
+    }
+
+    #[test]
+    fn test_iterations_are_reported_in_order() {
+        let mut depths = Vec::new();
+        let result = Searcher::new().search(&get_starting_board(), 3, |info| depths.extend(completed_depth(info)));
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(result.depth, 3);
+        assert_eq!(result.pv.len(), 3);
+        assert!(result.nodes > 20);
+    }
+
+    #[test]
+    fn test_iteration_statistics() {
+        let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut reports = Vec::new();
+        let result = Searcher::with_hash_size(1).search(&board, 5, |info| {
+            if let SearchInfo::Iteration(result) = info {
+                reports.push(result.clone());
+            }
+        });
+        assert_eq!(result.bound, Bound::Exact);
+        // The quiescence search goes past the nominal depth
+        assert!(result.seldepth > result.depth);
+        assert!(result.hashfull > 0);
+        assert_eq!(reports.last(), Some(&result));
+        assert!(reports.windows(2).all(|pair| pair[0].nodes <= pair[1].nodes && pair[0].time <= pair[1].time));
+        // Only a score outside the aspiration window is reported as a bound, and the iteration
+        // is then searched again
+        for (report, next) in reports.iter().zip(&reports[1..]) {
+            if report.bound != Bound::Exact {
+                assert_eq!(next.depth, report.depth);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fifty_move_rule_is_a_draw() {
+        // Only the pawn move keeps the extra queen from being drawn away
+        let result = search("4k3/8/8/8/8/8/4P3/Q3K3 w - - 99 80", 3);
+        assert_eq!(result.best_move().unwrap().src.to_string(), "e2");
+        assert!(result.score > 500);
+
+        // With no pawn to push and nothing to take, every move draws
+        let result = search("4k3/8/8/8/8/8/8/Q3K3 w - - 99 80", 3);
+        assert_eq!(result.score, 0);
+    }
+
+    #[test]
+    fn test_repetition_is_a_draw() {
+        // Down a queen, Black escapes by going back to the position the game started from
+        let mut board = Board::from_fen("7k/8/8/8/8/8/8/3QK1N1 w - - 0 1").unwrap();
+        board.apply_moves_from_strings(["g1f3", "h8g8", "f3g1"].iter().map(|s| s.to_string())).unwrap();
+        let result = Searcher::new().search(&board, 3, |_| {});
+        assert_eq!(result.best_move().unwrap().to_string(), "g8h8");
+        assert_eq!(result.score, 0);
+
+        // White, a queen up, steers clear of the same repetition
+        let mut board = Board::from_fen("7k/8/8/8/8/8/8/3QK1N1 w - - 0 1").unwrap();
+        board.apply_moves_from_strings(["g1f3", "h8g8", "f3g1", "g8h8", "g1f3", "h8g8"].iter().map(|s| s.to_string())).unwrap();
+        let result = Searcher::new().search(&board, 3, |_| {});
+        assert_ne!(result.best_move().unwrap().to_string(), "f3g1");
+        assert!(result.score > 500);
+    }
+
+    #[test]
+    fn test_quiescence_sees_the_recapture() {
+        // At depth 1 Qxe5+ wins a pawn, until dxe5 is taken into account
+        let result = search("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", 1);
+        assert_ne!(result.best_move().unwrap().to_string(), "e1e5");
+    }
+
+    #[test]
+    fn test_quiescence_finishes_the_exchange() {
+        // Taking on d5 is safe because the knight recapturing from c7 would be lost in turn
+        let result = search("4k3/2n5/8/3p4/8/1B6/8/3RK3 w - - 0 1", 1);
+        assert_eq!(result.best_move().unwrap().target.to_string(), "d5");
+    }
+
+    #[test]
+    fn test_move_ordering_saves_nodes() {
+        let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut ordered = Searcher::with_hash_size(1);
+        let mut unordered = Searcher::with_hash_size(1);
+        unordered.set_move_ordering(false);
+        let with = ordered.search(&board, 3, |_| {});
+        let without = unordered.search(&board, 3, |_| {});
+        assert!(with.nodes < without.nodes, "{} with ordering, {} without", with.nodes, without.nodes);
+        assert_eq!(with.score, without.score);
+    }
+
+    #[test]
+    fn test_hard_limit_abandons_the_iteration() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let budget = TimeBudget { soft: Duration::from_millis(20), hard: Duration::from_millis(20) };
+        let start = Instant::now();
+        let mut depths = Vec::new();
+        let result = Searcher::with_hash_size(1).search_with_budget(&board, 64, Some(budget), |info| depths.extend(completed_depth(info)));
+        assert!(start.elapsed() < Duration::from_secs(2), "took {:?}", start.elapsed());
+        // Only completed iterations are reported, and the deepest one is returned
+        assert_eq!(depths.last(), Some(&result.depth));
+        assert!(result.depth >= 1 && result.depth < 64);
+        assert!(result.best_move().is_some());
+    }
+
+    #[test]
+    fn test_first_iteration_always_completes() {
+        let budget = TimeBudget { soft: Duration::ZERO, hard: Duration::ZERO };
+        let result = Searcher::with_hash_size(1).search_with_budget(&get_starting_board(), 10, Some(budget), |_| {});
+        assert_eq!(result.depth, 1);
+        assert!(result.best_move().is_some());
+    }
+
+    #[test]
+    fn test_stop_flag_ends_the_search() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut searcher = Searcher::with_hash_size(1);
+        searcher.set_stop_flag(stop.clone());
+
+        // Raised before the search starts, only the first iteration is completed
+        stop.store(true, Ordering::Relaxed);
+        let result = searcher.search(&get_starting_board(), 64, |_| {});
+        assert_eq!(result.depth, 1);
+        assert!(result.best_move().is_some());
+        assert!(stop.load(Ordering::Relaxed));
+
+        // Raised from another thread during the search
+        stop.store(false, Ordering::Relaxed);
+        let raiser = {
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                stop.store(true, Ordering::Relaxed);
+            })
+        };
+        let result = searcher.search(&get_starting_board(), 64, |_| {});
+        raiser.join().unwrap();
+        assert!(result.depth < 64);
+        assert!(result.best_move().is_some());
+    }
+
+    #[test]
+    fn test_mate_scores_are_stored_relative_to_the_node() {
+        let mate_in_two = MATE_SCORE - 3;
+        assert_eq!(score_to_tt(mate_in_two, 2), MATE_SCORE - 1);
+        assert_eq!(score_from_tt(score_to_tt(mate_in_two, 2), 2), mate_in_two);
+        assert_eq!(score_from_tt(score_to_tt(-mate_in_two, 4), 4), -mate_in_two);
+        assert_eq!(score_to_tt(150, 6), 150);
+    }
+
+    #[test]
+    fn test_table_is_kept_between_searches() {
+        let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut searcher = Searcher::with_hash_size(1);
+        let first = searcher.search(&board, 3, |_| {});
+        let second = searcher.search(&board, 3, |_| {});
+        assert!(second.nodes < first.nodes, "{} then {}", first.nodes, second.nodes);
+        assert_eq!(second.best_move(), first.best_move());
+
+        searcher.clear_hash();
+        assert_eq!(searcher.search(&board, 3, |_| {}).nodes, first.nodes);
+    }
+
+    #[test]
+    fn test_game_over_has_no_best_move() {
+        let stalemate = search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 3);
+        assert_eq!(stalemate.best_move(), None);
+        assert_eq!(stalemate.score, 0);
+
+        let mated = search("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3", 3);
+        assert_eq!(mated.best_move(), None);
+        assert_eq!(mate_in(mated.score), Some(0));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::board::Color;
+use std::time::Duration;
+
+/// Time in milliseconds kept back on every move for communication and GUI lag, unless the
+/// GUI sets the `Move Overhead` option
+pub const DEFAULT_MOVE_OVERHEAD_MS: u64 = 10;
+/// Smallest and largest values the `Move Overhead` option accepts, in milliseconds
+pub const MIN_MOVE_OVERHEAD_MS: u64 = 0;
+pub const MAX_MOVE_OVERHEAD_MS: u64 = 5000;
+
+// Moves assumed to be left in the game when the GUI does not say
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+// Spreading the clock over more moves than this only wastes time early on
+const MAX_MOVES_TO_GO: u64 = 50;
+// Share of the remaining time a single move may ever use, in percent
+const MAX_USABLE_PERCENT: u64 = 80;
+// How far past the soft budget the search may run to finish an iteration
+const HARD_LIMIT_FACTOR: u32 = 3;
+
+/// Search limits given by the parameters of a UCI `go` command
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SearchLimits {
+    /// Time left on the clocks in milliseconds
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    /// Increment per move in milliseconds
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    /// Moves until the next time control
+    pub movestogo: Option<u64>,
+    /// Exact time to spend on this move in milliseconds
+    pub movetime: Option<u64>,
+    pub depth: Option<u32>,
+}
+
+impl SearchLimits {
+    /// Reads the limits from the parameters of a `go` command, ignoring any it does not know
+    /// or cannot parse
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chesslib::time_manager::SearchLimits;
+    /// let limits = SearchLimits::from_go_command("go wtime 60000 btime 58000 winc 1000 binc 1000");
+    ///
+    /// assert_eq!(limits.wtime, Some(60000));
+    /// assert_eq!(limits.binc, Some(1000));
+    /// assert_eq!(limits.movestogo, None);
+    /// ```
+    pub fn from_go_command(command: &str) -> SearchLimits {
+        let mut limits = SearchLimits::default();
+        let params: Vec<&str> = command.split_whitespace().collect();
+        for i in 0..params.len() {
+            let value = params.get(i + 1).and_then(|v| v.parse::<u64>().ok());
+            match params[i] {
+                "wtime" => limits.wtime = value,
+                "btime" => limits.btime = value,
+                "winc" => limits.winc = value,
+                "binc" => limits.binc = value,
+                "movestogo" => limits.movestogo = value,
+                "movetime" => limits.movetime = value,
+                "depth" => limits.depth = value.map(|depth| depth as u32),
+                _ => {}
+            }
+        }
+        limits
+    }
+}
+
+/// How long to think about one move
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TimeBudget {
+    /// Time after which no new iteration is started
+    pub soft: Duration,
+    /// Time after which the search is abandoned, even in the middle of an iteration
+    pub hard: Duration,
+}
+
+/// Splits the clock of the side to move into a budget for this move, or `None` when the
+/// search is not limited by time
+///
+/// With a `movetime` the whole of it, less the overhead, is used. Otherwise the remaining time
+/// is shared out over the moves to go, plus most of the increment, never touching the last
+/// fifth of the clock.
+///
+/// # Examples
+///
+/// ```
+/// use chesslib::board::Color;
+/// use chesslib::time_manager::{allocate, SearchLimits};
+/// use std::time::Duration;
+///
+/// let limits = SearchLimits { wtime: Some(30_000), movestogo: Some(10), ..SearchLimits::default() };
+/// let budget = allocate(&limits, Color::White, 0).unwrap();
+/// assert_eq!(budget.soft, Duration::from_millis(3000));
+/// assert_eq!(budget.hard, Duration::from_millis(9000));
+///
+/// // Black's clock was not given
+/// assert_eq!(allocate(&limits, Color::Black, 0), None);
+/// ```
+pub fn allocate(limits: &SearchLimits, side_to_move: Color, move_overhead_ms: u64) -> Option<TimeBudget> {
+    if let Some(movetime) = limits.movetime {
+        let budget = Duration::from_millis(movetime.saturating_sub(move_overhead_ms).max(1));
+        return Some(TimeBudget { soft: budget, hard: budget });
+    }
+
+    let (time, increment) = match side_to_move {
+        Color::White => (limits.wtime?, limits.winc.unwrap_or(0)),
+        Color::Black => (limits.btime?, limits.binc.unwrap_or(0)),
+    };
+    let remaining = time.saturating_sub(move_overhead_ms);
+    let usable = remaining * MAX_USABLE_PERCENT / 100;
+    let moves_to_go = limits.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).clamp(1, MAX_MOVES_TO_GO);
+
+    let soft = (remaining / moves_to_go + increment * 3 / 4).min(usable).max(1);
+    let hard = (soft * HARD_LIMIT_FACTOR as u64).min(usable).max(soft);
+    Some(TimeBudget { soft: Duration::from_millis(soft), hard: Duration::from_millis(hard) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(budget: TimeBudget) -> (u128, u128) {
+        (budget.soft.as_millis(), budget.hard.as_millis())
+    }
+
+    #[test]
+    fn test_from_go_command() {
+        let limits = SearchLimits::from_go_command("go wtime 1000 btime 2000 winc 10 binc 20 movestogo 5 depth 7");
+        assert_eq!(limits, SearchLimits {
+            wtime: Some(1000), btime: Some(2000), winc: Some(10), binc: Some(20),
+            movestogo: Some(5), movetime: None, depth: Some(7),
+        });
+        assert_eq!(SearchLimits::from_go_command("go movetime 500").movetime, Some(500));
+        assert_eq!(SearchLimits::from_go_command("go wtime"), SearchLimits::default());
+        assert_eq!(SearchLimits::from_go_command("go wtime -5"), SearchLimits::default());
+    }
+
+    #[test]
+    fn test_movetime_is_used_in_full() {
+        let limits = SearchLimits { movetime: Some(1000), wtime: Some(5), ..SearchLimits::default() };
+        assert_eq!(millis(allocate(&limits, Color::Black, 50).unwrap()), (950, 950));
+        let tiny = SearchLimits { movetime: Some(10), ..SearchLimits::default() };
+        assert_eq!(millis(allocate(&tiny, Color::White, 50).unwrap()), (1, 1));
+    }
+
+    #[test]
+    fn test_clock_is_shared_over_moves_to_go() {
+        let limits = SearchLimits { wtime: Some(60_000), btime: Some(30_000), ..SearchLimits::default() };
+        assert_eq!(millis(allocate(&limits, Color::White, 0).unwrap()), (2000, 6000));
+        assert_eq!(millis(allocate(&limits, Color::Black, 0).unwrap()), (1000, 3000));
+        // The overhead comes off the clock first
+        assert_eq!(millis(allocate(&limits, Color::White, 3000).unwrap()), (1900, 5700));
+    }
+
+    #[test]
+    fn test_increment_is_mostly_spent() {
+        let limits = SearchLimits { btime: Some(3_000), binc: Some(2_000), ..SearchLimits::default() };
+        assert_eq!(millis(allocate(&limits, Color::Black, 0).unwrap()), (1600, 2400));
+    }
+
+    #[test]
+    fn test_last_move_before_time_control_keeps_a_reserve() {
+        let limits = SearchLimits { wtime: Some(10_000), movestogo: Some(1), ..SearchLimits::default() };
+        assert_eq!(millis(allocate(&limits, Color::White, 0).unwrap()), (8000, 8000));
+    }
+
+    #[test]
+    fn test_no_time_limit() {
+        assert_eq!(allocate(&SearchLimits::default(), Color::White, 0), None);
+        let depth_only = SearchLimits { depth: Some(5), ..SearchLimits::default() };
+        assert_eq!(allocate(&depth_only, Color::White, 0), None);
+    }
+
+    #[test]
+    fn test_flagging_clock_still_gets_a_budget() {
+        let limits = SearchLimits { wtime: Some(5), ..SearchLimits::default() };
+        assert_eq!(millis(allocate(&limits, Color::White, 10).unwrap()), (1, 1));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::board::Move;
+
+/// Table size in megabytes used unless the GUI sets the `Hash` option
+pub const DEFAULT_HASH_SIZE_MB: usize = 16;
+/// Smallest and largest sizes the `Hash` option accepts, in megabytes
+pub const MIN_HASH_SIZE_MB: usize = 1;
+pub const MAX_HASH_SIZE_MB: usize = 1024;
+
+/// How a stored score relates to the position's true score
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Bound {
+    /// The score is exact, it fell between alpha and beta
+    Exact,
+    /// The search failed high, the true score is at least this
+    Lower,
+    /// The search failed low, the true score is at most this
+    Upper,
+}
+
+/// What a search of one position found
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TtEntry {
+    /// Full Zobrist key, to tell the position apart from others sharing its slot
+    pub key: u64,
+    pub depth: u32,
+    pub bound: Bound,
+    pub score: i32,
+    pub best_move: Option<Move>,
+    // Search the entry was written in, so entries left over from earlier moves get replaced
+    generation: u8,
+}
+
+/// Fixed-size hash table of search results indexed by Zobrist key
+pub struct TranspositionTable {
+    entries: Vec<Option<TtEntry>>,
+    generation: u8,
+}
+
+impl TranspositionTable {
+    /// Creates a table using at most `size_mb` megabytes
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let mut table = TranspositionTable { entries: Vec::new(), generation: 0 };
+        table.resize(size_mb);
+        table
+    }
+
+    /// Reallocates the table to use at most `size_mb` megabytes, dropping every entry
+    pub fn resize(&mut self, size_mb: usize) {
+        let size_mb = size_mb.clamp(MIN_HASH_SIZE_MB, MAX_HASH_SIZE_MB);
+        let len = size_mb * 1024 * 1024 / std::mem::size_of::<Option<TtEntry>>();
+        self.entries = vec![None; len];
+        self.generation = 0;
+    }
+
+    /// Forgets every stored position, as at the start of a new game
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.generation = 0;
+    }
+
+    /// Marks the start of a new search, making the entries from earlier ones replaceable
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// Number of entries the table can hold
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// How full the table is, in permille, from a sample of its first thousand slots; only
+    /// entries written by the current search count, since older ones are free to be replaced
+    pub fn hashfull(&self) -> u32 {
+        let sample = &self.entries[..self.entries.len().min(1000)];
+        let used = sample.iter().flatten().filter(|entry| entry.generation == self.generation).count();
+        (used * 1000 / sample.len()) as u32
+    }
+
+    /// Returns what is known about the position with the given key
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        self.entries[self.index(key)].filter(|entry| entry.key == key)
+    }
+
+    /// Stores a search result, unless the slot holds a deeper search of another position from
+    /// the current search
+    pub fn store(&mut self, key: u64, depth: u32, bound: Bound, score: i32, best_move: Option<Move>) {
+        let index = self.index(key);
+        let generation = self.generation;
+        let replace = match &self.entries[index] {
+            None => true,
+            Some(old) => old.key == key || old.generation != generation || depth >= old.depth,
+        };
+        if replace {
+            self.entries[index] = Some(TtEntry { key, depth, bound, score, best_move, generation });
+        }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+}
+
+// The entries are far too many to print
+impl std::fmt::Debug for TranspositionTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TranspositionTable")
+            .field("capacity", &self.capacity())
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> TranspositionTable {
+        TranspositionTable::new(DEFAULT_HASH_SIZE_MB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Square;
+
+    #[test]
+    fn test_size_follows_megabytes() {
+        let small = TranspositionTable::new(1);
+        let large = TranspositionTable::new(4);
+        assert!(small.capacity() > 0);
+        assert_eq!(large.capacity() / small.capacity(), 4);
+        // Out of range sizes are clamped
+        assert_eq!(TranspositionTable::new(0).capacity(), small.capacity());
+    }
+
+    #[test]
+    fn test_hashfull_counts_the_current_search() {
+        let mut table = TranspositionTable::new(1);
+        assert_eq!(table.hashfull(), 0);
+        for key in 0..500 {
+            table.store(key, 1, Bound::Exact, 0, None);
+        }
+        assert_eq!(table.hashfull(), 500);
+        table.new_search();
+        assert_eq!(table.hashfull(), 0);
+    }
+
+    #[test]
+    fn test_store_and_probe() {
+        let mut table = TranspositionTable::new(1);
+        let mv = Move::new(Square::E2, Square::E4);
+        table.store(12345, 3, Bound::Exact, 42, Some(mv));
+
+        let entry = table.probe(12345).unwrap();
+        assert_eq!((entry.depth, entry.bound, entry.score, entry.best_move), (3, Bound::Exact, 42, Some(mv)));
+        assert_eq!(table.probe(54321), None);
+
+        // A different key landing in the same slot is not mistaken for the stored one
+        let colliding = 12345 + table.capacity() as u64;
+        assert_eq!(table.probe(colliding), None);
+
+        table.clear();
+        assert_eq!(table.probe(12345), None);
+    }
+
+    #[test]
+    fn test_replacement_prefers_depth_within_a_search() {
+        let mut table = TranspositionTable::new(1);
+        let colliding = 7 + table.capacity() as u64;
+        table.store(7, 5, Bound::Exact, 1, None);
+
+        // A shallower result for another position does not evict the deeper one
+        table.store(colliding, 2, Bound::Lower, 2, None);
+        assert_eq!(table.probe(7).unwrap().depth, 5);
+        assert_eq!(table.probe(colliding), None);
+
+        // The same position is always updated
+        table.store(7, 1, Bound::Upper, 3, None);
+        assert_eq!(table.probe(7).unwrap().bound, Bound::Upper);
+
+        // Entries from an earlier search make way
+        table.store(7, 5, Bound::Exact, 1, None);
+        table.new_search();
+        table.store(colliding, 2, Bound::Lower, 2, None);
+        assert_eq!(table.probe(colliding).unwrap().score, 2);
+        assert_eq!(table.probe(7), None);
+    }
+}
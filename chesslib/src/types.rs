@@ -1,4 +1,4 @@
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Square {
     A1, B1, C1, D1, E1, F1, G1, H1,
     A2, B2, C2, D2, E2, F2, G2, H2,
@@ -10,16 +10,49 @@ pub enum Square {
     A8, B8, C8, D8, E8, F8, G8, H8,
 }
 
+/// All squares in bit index order, so `ALL_SQUARES[i].to_bit_index() == i`
+pub const ALL_SQUARES: [Square; 64] = [
+    Square::A1, Square::B1, Square::C1, Square::D1, Square::E1, Square::F1, Square::G1, Square::H1,
+    Square::A2, Square::B2, Square::C2, Square::D2, Square::E2, Square::F2, Square::G2, Square::H2,
+    Square::A3, Square::B3, Square::C3, Square::D3, Square::E3, Square::F3, Square::G3, Square::H3,
+    Square::A4, Square::B4, Square::C4, Square::D4, Square::E4, Square::F4, Square::G4, Square::H4,
+    Square::A5, Square::B5, Square::C5, Square::D5, Square::E5, Square::F5, Square::G5, Square::H5,
+    Square::A6, Square::B6, Square::C6, Square::D6, Square::E6, Square::F6, Square::G6, Square::H6,
+    Square::A7, Square::B7, Square::C7, Square::D7, Square::E7, Square::F7, Square::G7, Square::H7,
+    Square::A8, Square::B8, Square::C8, Square::D8, Square::E8, Square::F8, Square::G8, Square::H8,
+];
+
 impl Square {
     pub fn to_bit_index(&self) -> u8 {
         *self as u8
     }
 
+    /// Returns the square for a bit index in the range 0..64
+    pub fn from_bit_index(index: u8) -> Square {
+        ALL_SQUARES[index as usize]
+    }
+
+    /// File of the square, 0 for the a-file up to 7 for the h-file
+    pub fn file(&self) -> u8 {
+        self.to_bit_index() % 8
+    }
+
+    /// Rank of the square, 0 for the first rank up to 7 for the eighth rank
+    pub fn rank(&self) -> u8 {
+        self.to_bit_index() / 8
+    }
+
     pub fn to_bitboard(&self) -> u64 {
         1u64 << self.to_bit_index()
     }
 }
 
+impl std::fmt::Display for Square {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}", (b'a' + self.file()) as char, self.rank() + 1)
+    }
+}
+
 // Implement a conversion from a string coordinate (e.g., "a1") to a Square.
 use std::convert::TryFrom;
 
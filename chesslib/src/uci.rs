@@ -1,119 +1,692 @@
-use crate::board::{Board, get_starting_board};
-use std::sync::Mutex;
+use crate::board::{Board, GameResult, NULL_MOVE, get_starting_board};
+use crate::perft::PerftCounts;
+use crate::search::{mate_in, SearchInfo, Searcher, DEFAULT_DEPTH, MAX_PLY};
+use crate::logger::Logger;
+use crate::options::{engine_options, Options, CLEAR_HASH, DEBUG_LOG_FILE, HASH, MOVE_OVERHEAD};
+use crate::time_manager::{allocate, SearchLimits, TimeBudget, DEFAULT_MOVE_OVERHEAD_MS};
+use crate::transposition::Bound;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use lazy_static::lazy_static;
 
 lazy_static! {
-    static ref BOARD_STATE: Mutex<Option<Board>> = Mutex::new(None);
+    // Engine behind `handle_uci_command`, for callers that only ever need the one
+    static ref ENGINE: Mutex<UciEngine> = Mutex::new(UciEngine::new());
 }
 
-pub fn handle_uci_command(input: &str) -> String {
-    match input.trim() {
-        "uci" => "id name ChessEngine\nid author YourName\nuciok".to_string(),
-        "isready" => "readyok".to_string(),
-        "quit" => "".to_string(),
-        "ucinewgame" => {
-            let mut board_state = BOARD_STATE.lock().unwrap();
-            *board_state = Some(get_starting_board()); // Reset the board state
-            "".to_string()
-        },
-        command if command.starts_with("position") => {
-            let mut board_state = BOARD_STATE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-            // Always reset to starting position when "startpos" is used
-            if command.contains("startpos") {
-                *board_state = Some(get_starting_board());
-                if let Some(board) = board_state.as_mut() {
-                    if let Some(moves_str) = command.strip_prefix("position startpos moves ") {
-                        let moves = moves_str.split_whitespace();
-                        board.apply_moves_from_strings(moves.map(|s| s.to_string()));
+/// A UCI engine: the position, the options and the search, answering one command at a time
+///
+/// Engines are independent of each other, so several can play in one process. `go` starts the
+/// search on a worker thread and returns straight away; the worker's `info` lines and its
+/// `bestmove` go to the search output, standard output unless set otherwise. Everything
+/// received and sent goes to the log, once the `Debug Log File` option names one.
+///
+/// # Examples
+///
+/// ```
+/// use chesslib::uci::UciEngine;
+/// use std::sync::mpsc;
+///
+/// let (output, search_lines) = mpsc::channel();
+/// let mut engine = UciEngine::new();
+/// engine.set_search_output(Some(output));
+/// assert_eq!(engine.handle("isready"), vec!["readyok"]);
+/// engine.handle("position startpos moves e2e4");
+/// assert!(engine.handle("go depth 3").is_empty());
+/// assert!(search_lines.iter().any(|line| line.starts_with("bestmove ")));
+/// ```
+#[derive(Debug)]
+pub struct UciEngine {
+    // Position set by the last `position` command, if any
+    board: Option<Board>,
+    options: Options,
+    // Away on the worker thread while a search runs, which hands it back when done
+    searcher: Option<Searcher>,
+    search_thread: Option<JoinHandle<Searcher>>,
+    // Raised by `stop` to end the running search
+    stop: Arc<AtomicBool>,
+    search_output: Option<Sender<String>>,
+    // Shared with the worker thread, which logs what it sends
+    logger: Arc<Mutex<Logger>>,
+    // Set by `debug on`, for `info string` lines explaining what the engine does
+    debug: bool,
+}
+
+impl Default for UciEngine {
+    fn default() -> UciEngine {
+        UciEngine::new()
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        self.stop_search();
+    }
+}
+
+impl UciEngine {
+    pub fn new() -> UciEngine {
+        let options = engine_options();
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut searcher = Searcher::with_hash_size(options.spin(HASH).unwrap_or_default() as usize);
+        searcher.set_stop_flag(stop.clone());
+        UciEngine {
+            board: None,
+            options,
+            searcher: Some(searcher),
+            search_thread: None,
+            stop,
+            search_output: None,
+            logger: Arc::new(Mutex::new(Logger::new())),
+            debug: false,
+        }
+    }
+
+    /// Sends the lines written by the search thread, its `info` lines and `bestmove`, to
+    /// `output` rather than printing them to standard output
+    ///
+    /// Passing `None` goes back to printing, and drops the previous sender so whoever holds
+    /// the receiving end sees the output come to an end once the running search is done.
+    pub fn set_search_output(&mut self, output: Option<Sender<String>>) {
+        self.search_output = output;
+    }
+
+    /// Returns the FEN of the position set by the last `position` command, if any
+    pub fn fen(&self) -> Option<String> {
+        self.board.as_ref().map(|board| board.to_fen())
+    }
+
+    /// The options as currently set by the GUI
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Logs the conversation to the end of the file at `path`, as if the GUI had set the
+    /// `Debug Log File` option; an empty path turns logging off
+    pub fn set_log_file(&mut self, path: &str) -> Result<(), String> {
+        let opened = lock(&self.logger).open(path);
+        // A file that cannot be opened leaves logging off
+        self.options.set(DEBUG_LOG_FILE, Some(if opened.is_ok() { path } else { "" }))?;
+        opened.map_err(|e| format!("cannot open log file {}: {}", path, e))
+    }
+
+    /// Handles one line from the GUI and returns the lines to answer with, often none
+    pub fn handle(&mut self, line: &str) -> Vec<String> {
+        lock(&self.logger).input(line.trim());
+        let response = self.respond(line);
+        let mut logger = lock(&self.logger);
+        response.iter().for_each(|line| logger.output(line));
+        response
+    }
+
+    fn respond(&mut self, line: &str) -> Vec<String> {
+        match line.trim() {
+            "uci" => {
+                let mut lines = vec!["id name ChessEngine".to_string(), "id author YourName".to_string()];
+                lines.extend(self.options.iter().map(|option| option.uci_line()));
+                lines.push("uciok".to_string());
+                lines
+            },
+            "isready" => vec!["readyok".to_string()],
+            "debug on" | "debug off" => {
+                self.debug = line.trim() == "debug on";
+                Vec::new()
+            },
+            "quit" | "stop" => {
+                self.stop_search();
+                Vec::new()
+            },
+            "ucinewgame" => {
+                self.board = Some(get_starting_board()); // Reset the board state
+                self.searcher().clear_hash();
+                Vec::new()
+            },
+            command if command.starts_with("setoption") => self.set_option(command).into_iter().collect(),
+            command if command.starts_with("position") => {
+                let tokens: Vec<&str> = command.split_whitespace().skip(1).collect();
+                let moves_index = tokens.iter().position(|&token| token == "moves").unwrap_or(tokens.len());
+                let (setup, moves) = tokens.split_at(moves_index);
+
+                let mut board = match setup.first() {
+                    // Always reset to starting position when "startpos" is used
+                    Some(&"startpos") => get_starting_board(),
+                    Some(&"fen") => match Board::from_fen(&setup[1..].join(" ")) {
+                        Ok(board) => board,
+                        Err(e) => return vec![format!("info string invalid fen: {}", e)],
+                    },
+                    _ => self.board.take().unwrap_or_else(get_starting_board),
+                };
+                // A move that cannot be played ends the list, rather than the engine
+                let mut response = vec!["position set".to_string()];
+                for mv in moves.iter().skip(1) {
+                    if let Err(e) = board.apply_move_from_string(mv) {
+                        response.push(format!("info string invalid move {}: {}", mv, e));
+                        break;
                     }
                 }
-            } else {
-                // Handle other position commands (like FEN) here if needed
-                if board_state.is_none() {
-                    *board_state = Some(get_starting_board());
+                let fen = board.to_fen();
+                lock(&self.logger).note(&format!("Position: {}", fen));
+                response.extend(self.diagnostic(|| format!("position {}", fen)));
+                self.board = Some(board);
+                response
+            },
+            command if command.starts_with("go perft") => {
+                self.stop_search();
+                match command.split_whitespace().nth(2).and_then(|v| v.parse::<u32>().ok()) {
+                    Some(depth) => perft_response(self.board.as_ref().unwrap_or(&get_starting_board()), depth),
+                    None => vec!["info string go perft needs a depth".to_string()],
                 }
+            },
+            command if command.starts_with("go") => self.go(command).into_iter().collect(),
+            _ => vec!["Unknown command".to_string()],
+        }
+    }
+
+    // Starts searching on a worker thread, so commands keep being answered in the meantime.
+    // An infinite search holds back its best move until `stop`, as UCI requires, even if it
+    // runs out of depth first.
+    fn go(&mut self, command: &str) -> Option<String> {
+        self.stop_search();
+        let mut searcher = self.searcher.take().expect("the searcher is back once the search has stopped");
+        // Without a position, play from the start
+        let board = self.board.clone().unwrap_or_else(get_starting_board);
+        let limits = SearchLimits::from_go_command(command);
+        let infinite = command.split_whitespace().any(|token| token == "infinite");
+        let overhead = self.options.spin(MOVE_OVERHEAD).map_or(DEFAULT_MOVE_OVERHEAD_MS, |overhead| overhead as u64);
+        let budget = if infinite { None } else { allocate(&limits, board.side_to_move, overhead) };
+        // Timed and infinite searches go as deep as they are allowed to
+        let depth = limits.depth.unwrap_or(if budget.is_some() || infinite { MAX_PLY as u32 } else { DEFAULT_DEPTH });
+
+        self.stop.store(false, Ordering::Relaxed);
+        let diagnostic = self.diagnostic(|| match budget {
+            Some(budget) => format!("searching to depth {} in {} ms, {} ms at most",
+                depth, budget.soft.as_millis(), budget.hard.as_millis()),
+            None => format!("searching to depth {} without a time limit", depth),
+        });
+
+        let stop = self.stop.clone();
+        let output = self.search_output.clone();
+        let logger = self.logger.clone();
+        let emit = move |line: String| {
+            lock(&logger).output(&line);
+            match &output {
+                Some(output) => { let _ = output.send(line); },
+                None => println!("{}", line),
             }
-            "position set".to_string()
-        },
-        command if command.starts_with("go") => {
-            let board_state = BOARD_STATE.lock().unwrap();
-            if let Some(board) = board_state.as_ref() {
-                let mut _wtime: Option<u32> = None;
-                let mut _btime: Option<u32> = None;
-                let mut _movestogo: Option<u32> = None;
-
-                // Parse parameters
-                let params: Vec<&str> = command.split_whitespace().collect();
-                for i in 0..params.len() {
-                    match params[i] {
-                        "wtime" => _wtime = params.get(i + 1).and_then(|v| v.parse::<u32>().ok()),
-                        "btime" => _btime = params.get(i + 1).and_then(|v| v.parse::<u32>().ok()),
-                        "movestogo" => _movestogo = params.get(i + 1).and_then(|v| v.parse::<u32>().ok()),
-                        _ => {}
-                    }
-                }
+        };
+        self.search_thread = Some(thread::spawn(move || {
+            let bestmove = search_and_report(&board, &mut searcher, depth, budget, &emit);
+            while infinite && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            emit(bestmove);
+            searcher
+        }));
+        diagnostic
+    }
+
+    // An `info string` line for the GUI, only made when debugging is on
+    fn diagnostic(&self, message: impl FnOnce() -> String) -> Option<String> {
+        self.debug.then(|| format!("info string {}", message()))
+    }
+
+    /// Ends the running search, if any, and waits for it to send its best move
+    fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(search_thread) = self.search_thread.take() {
+            // Should the search have panicked, start over with a fresh searcher
+            let searcher = search_thread.join().unwrap_or_else(|_| {
+                let mut searcher = Searcher::with_hash_size(self.options.spin(HASH).unwrap_or_default() as usize);
+                searcher.set_stop_flag(self.stop.clone());
+                searcher
+            });
+            self.searcher = Some(searcher);
+        }
+    }
+
+    // The searcher, once back from the worker thread
+    fn searcher(&mut self) -> &mut Searcher {
+        self.stop_search();
+        self.searcher.as_mut().expect("the searcher is back once the search has stopped")
+    }
+
+    /// Handles `setoption name <id> [value <x>]`, which gets no reply unless it is not
+    /// understood
+    fn set_option(&mut self, command: &str) -> Option<String> {
+        let tokens: Vec<&str> = command.split_whitespace().skip(1).collect();
+        let value_index = tokens.iter().position(|&token| token == "value").unwrap_or(tokens.len());
+        if tokens.first() != Some(&"name") {
+            return Some("info string setoption needs a name".to_string());
+        }
+        // Option names may contain spaces and are matched case-insensitively
+        let name = tokens[1..value_index].join(" ");
+        let value = tokens.get(value_index + 1..).map(|value| value.join(" "));
+
+        let changed = match self.options.set(&name, value.as_deref()) {
+            Ok(DEBUG_LOG_FILE) => {
+                let path = self.options.string(DEBUG_LOG_FILE).unwrap_or_default().to_string();
+                self.set_log_file(&path).map(|_| DEBUG_LOG_FILE)
+            },
+            changed => changed,
+        };
+        match changed {
+            Ok(HASH) => {
+                let size_mb = self.options.spin(HASH).unwrap_or_default() as usize;
+                self.searcher().set_hash_size(size_mb);
+            },
+            Ok(CLEAR_HASH) => self.searcher().clear_hash(),
+            // The rest are read when needed
+            Ok(_) => {},
+            Err(message) => return Some(format!("info string {}", message)),
+        }
+        self.diagnostic(|| match value {
+            Some(value) => format!("{} set to {}", name, value),
+            None => format!("{} set", name),
+        })
+    }
+}
+
+// The logger stays usable even if a thread panicked while holding it
+fn lock(logger: &Mutex<Logger>) -> std::sync::MutexGuard<'_, Logger> {
+    logger.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Passes `input` to a process-wide `UciEngine` and returns its answer as one string, the
+/// lines separated by newlines
+pub fn handle_uci_command(input: &str) -> String {
+    ENGINE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).handle(input).join("\n")
+}
+
+/// Runs the search for `go`, passing an info line for each iteration to `emit`, and returns
+/// the `bestmove` line
+///
+/// A finished game is reported with a mate or draw score and the null move rather than
+/// leaving the GUI waiting for a move that does not exist.
+fn search_and_report(board: &Board, searcher: &mut Searcher, depth: u32, budget: Option<TimeBudget>,
+                     mut emit: impl FnMut(String)) -> String {
+    match board.game_result() {
+        Some(GameResult::Checkmate { .. }) => emit("info depth 0 score mate 0".to_string()),
+        Some(GameResult::Stalemate) => emit("info depth 0 score cp 0".to_string()),
+        // Fifty-move and repetition draws are only draws once claimed, and the GUI may still
+        // want a move
+        Some(GameResult::FiftyMoveRule | GameResult::ThreefoldRepetition) | None => {
+            let result = searcher.search_with_budget(board, depth, budget, |info| emit(info_line(&info)));
+            if let Some(best_move) = result.best_move() {
+                return format!("bestmove {}", best_move);
+            }
+        }
+    }
+    format!("bestmove {}", NULL_MOVE)
+}
 
-                format!("bestmove {}", board.get_next_move())
-            } else {
-                "bestmove e2e4".to_string() // Default move if no position is set
+/// Formats what the search reports as a UCI `info` line
+fn info_line(info: &SearchInfo) -> String {
+    match info {
+        SearchInfo::Iteration(result) => {
+            let mut line = format!("info depth {} seldepth {} score ", result.depth, result.seldepth);
+            match mate_in(result.score) {
+                Some(moves) => line += &format!("mate {}", moves),
+                None => line += &format!("cp {}", result.score),
+            }
+            match result.bound {
+                Bound::Lower => line += " lowerbound",
+                Bound::Upper => line += " upperbound",
+                Bound::Exact => {},
+            }
+            line += &format!(" {}", progress(result.nodes, result.time, result.hashfull));
+            // A score that fell low comes without a best line
+            if !result.pv.is_empty() {
+                let pv: Vec<String> = result.pv.iter().map(|mv| mv.to_string()).collect();
+                line += &format!(" pv {}", pv.join(" "));
             }
+            line
         },
-        "stop" => "calculation stopped".to_string(),
-        _ => "Unknown command".to_string(),
+        SearchInfo::CurrentMove { depth, mv, number } => format!("info depth {} currmove {} currmovenumber {}", depth, mv, number),
+        SearchInfo::Progress { nodes, time, hashfull } => format!("info {}", progress(*nodes, *time, *hashfull)),
     }
 }
 
+fn progress(nodes: u64, time: Duration, hashfull: u32) -> String {
+    let ms = time.as_millis() as u64;
+    let nps = nodes * 1000 / ms.max(1);
+    format!("nodes {} nps {} time {} hashfull {}", nodes, nps, ms, hashfull)
+}
+
+/// Reply to the `go perft <depth>` extension: the node count below each root move, then
+/// the total with its breakdown by move type
+fn perft_response(board: &Board, depth: u32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut total = PerftCounts::default();
+    for (mv, counts) in board.divide_counts(depth) {
+        lines.push(format!("{}: {}", mv, counts.nodes));
+        total += counts;
+    }
+    if depth == 0 {
+        total.nodes = 1;
+    }
+    lines.push(String::new());
+    lines.push(format!("Nodes searched: {}", total.nodes));
+    lines.push(format!("Captures: {} En passant: {} Castles: {} Promotions: {} Checks: {} Checkmates: {}",
+        total.captures, total.en_passants, total.castles, total.promotions, total.checks, total.checkmates));
+    lines
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::board::Move;
+    use crate::search::SearchResult;
+    use crate::types::Square;
+    use std::sync::mpsc;
+    use std::time::Instant;
+
+    fn engine() -> UciEngine {
+        let mut engine = UciEngine::new();
+        engine.handle("setoption name Hash value 1");
+        engine
+    }
+
+    // Collects what `search_and_report` would send to the GUI
+    fn best_move_response(board: &Board, searcher: &mut Searcher, depth: u32, budget: Option<TimeBudget>) -> String {
+        let mut lines = Vec::new();
+        let bestmove = search_and_report(board, searcher, depth, budget, |line| lines.push(line));
+        lines.push(bestmove);
+        lines.join("\n")
+    }
 
     #[test]
     fn test_handle_uci_command() {
-        assert_eq!(handle_uci_command("uci"), "id name ChessEngine\nid author YourName\nuciok");
+        assert_eq!(handle_uci_command("uci"), "id name ChessEngine\nid author YourName\n\
+            option name Hash type spin default 16 min 1 max 1024\n\
+            option name Clear Hash type button\n\
+            option name Move Overhead type spin default 10 min 0 max 5000\n\
+            option name Debug Log File type string default <empty>\nuciok");
         assert_eq!(handle_uci_command("isready"), "readyok");
-        assert_eq!(handle_uci_command("quit"), "");
         assert_eq!(handle_uci_command("unknown"), "Unknown command");
-        assert_eq!(handle_uci_command("position"), "position set");
     }
 
     #[test]
-    fn test_handle_uci_newgame() {
-        assert_eq!(handle_uci_command("ucinewgame"), "");
+    fn test_handle_simple_commands() {
+        let mut engine = engine();
+        assert_eq!(engine.handle("isready"), vec!["readyok"]);
+        assert_eq!(engine.handle("ucinewgame"), Vec::<String>::new());
+        assert_eq!(engine.handle("stop"), Vec::<String>::new());
+        assert_eq!(engine.handle("quit"), Vec::<String>::new());
+        assert_eq!(engine.handle("unknown"), vec!["Unknown command"]);
+        assert_eq!(engine.handle("position"), vec!["position set"]);
+    }
+
+    #[test]
+    fn test_handle_position() {
+        let mut engine = engine();
+        assert_eq!(engine.fen(), None);
+        assert_eq!(engine.handle("position startpos moves e2e4"), vec!["position set"]);
+        assert_eq!(engine.fen().unwrap(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    }
+
+    #[test]
+    fn test_handle_position_invalid_moves() {
+        let mut engine = engine();
+        // Black cannot move first, so the moves stop there
+        assert_eq!(engine.handle("position startpos moves e7e5 e2e4"),
+            vec!["position set", "info string invalid move e7e5: Illegal move"]);
+        assert_eq!(engine.fen(), Some(get_starting_board().to_fen()));
+
+        assert_eq!(engine.handle("position startpos moves e2e4 e5 d7d5"),
+            vec!["position set", "info string invalid move e5: Invalid move format"]);
+        assert_eq!(engine.fen().unwrap(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    }
+
+    #[test]
+    fn test_engines_are_independent() {
+        let mut first = engine();
+        let mut second = engine();
+        first.handle("position startpos moves e2e4");
+        second.handle("position fen 4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        first.handle("setoption name Move Overhead value 30");
+        assert!(first.fen().unwrap().starts_with("rnbqkbnr/pppppppp/8/8/4P3/"));
+        assert!(second.fen().unwrap().starts_with("4k3/8/8/8/8/8/8/4K3 w"));
+        assert_eq!(first.options().spin(MOVE_OVERHEAD), Some(30));
+        assert_eq!(second.options().spin(MOVE_OVERHEAD), Some(10));
+    }
+
+    #[test]
+    fn test_go_searches_in_the_background() {
+        let (sender, receiver) = mpsc::channel();
+        let mut engine = engine();
+        engine.set_search_output(Some(sender));
+        let next_bestmove = || loop {
+            let line = receiver.recv_timeout(Duration::from_secs(10)).expect("search should answer");
+            if line.starts_with("bestmove") {
+                return line;
+            }
+        };
+
+        // Black to move after d2d4, so the answer comes from rank 7 (pawn) or rank 8 (knight)
+        engine.handle("position startpos moves e2e4 e7e5");
+        engine.handle("position startpos moves d2d4");
+        assert!(engine.handle("go depth 2").is_empty());
+        let bestmove = next_bestmove();
+        let rank = bestmove.split_whitespace().nth(1).unwrap().chars().nth(1).unwrap();
+        assert!(rank == '7' || rank == '8', "Got {}", bestmove);
+
+        // An infinite search only gives its move once told to stop, and keeps answering isready
+        engine.handle("position startpos");
+        assert!(engine.handle("go infinite").is_empty());
+        assert_eq!(engine.handle("isready"), vec!["readyok"]);
+        thread::sleep(Duration::from_millis(50));
+        assert!(receiver.try_iter().all(|line| !line.starts_with("bestmove")));
+        assert!(engine.handle("stop").is_empty());
+        assert!(next_bestmove() != "bestmove 0000");
+    }
+
+    #[test]
+    fn test_go_knows_the_moves_of_the_position_command() {
+        let (sender, receiver) = mpsc::channel();
+        let mut engine = engine();
+        engine.set_search_output(Some(sender));
+        // Down a queen, Black draws by repeating the position the moves started from
+        engine.handle("position fen 7k/8/8/8/8/8/8/3QK1N1 w - - 0 1 moves g1f3 h8g8 f3g1");
+        engine.handle("go depth 3");
+        let lines: Vec<String> = receiver.iter().take_while(|line| !line.starts_with("bestmove")).collect();
+        assert!(lines.last().unwrap().contains(" score cp 0 "), "Got {:?}", lines);
+        assert!(lines.last().unwrap().ends_with(" pv g8h8"), "Got {:?}", lines);
+    }
+
+    #[test]
+    fn test_dropping_the_engine_ends_the_search() {
+        let (sender, receiver) = mpsc::channel();
+        let mut engine = engine();
+        engine.set_search_output(Some(sender));
+        engine.handle("go infinite");
+        drop(engine);
+        assert!(receiver.iter().last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn test_handle_uci_position_fen() {
+        let mut engine = engine();
+        assert_eq!(engine.handle("position fen 4k3/8/8/8/8/8/8/4K3 w - - 0 1"), vec!["position set"]);
+        assert_eq!(engine.handle("position fen 4k3/8/8/8/8/8/8/4K3 w - - 0 1 moves e1d1 e8d8"), vec!["position set"]);
+        assert_eq!(engine.fen().unwrap(), "3k4/8/8/8/8/8/8/3K4 w - - 2 2");
+    }
+
+    #[test]
+    fn test_best_move_response_when_game_is_over() {
+        let mated = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert_eq!(best_move_response(&mated, &mut Searcher::with_hash_size(1), 3, None), "info depth 0 score mate 0\nbestmove 0000");
+
+        let stalemated = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(best_move_response(&stalemated, &mut Searcher::with_hash_size(1), 3, None), "info depth 0 score cp 0\nbestmove 0000");
+    }
+
+    #[test]
+    fn test_best_move_response_reports_each_iteration() {
+        let response = best_move_response(&get_starting_board(), &mut Searcher::with_hash_size(1), 2, None);
+        let lines: Vec<&str> = response.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("info depth 1 seldepth "), "Got {}", lines[0]);
+        assert!(lines[1].starts_with("info depth 2 seldepth "), "Got {}", lines[1]);
+        for field in [" score cp ", " nodes ", " nps ", " time ", " hashfull ", " pv "] {
+            assert!(lines[1].contains(field), "{} missing from {}", field, lines[1]);
+        }
+        assert!(lines[2].starts_with("bestmove ") && lines[2] != "bestmove 0000");
+    }
+
+    #[test]
+    fn test_info_line() {
+        let e2e4 = Move::new(Square::E2, Square::E4);
+        let mut result = SearchResult {
+            depth: 6, seldepth: 11, score: 35, bound: Bound::Exact, pv: vec![e2e4], nodes: 50_000,
+            time: Duration::from_millis(250), hashfull: 12,
+        };
+        assert_eq!(info_line(&SearchInfo::Iteration(&result)),
+            "info depth 6 seldepth 11 score cp 35 nodes 50000 nps 200000 time 250 hashfull 12 pv e2e4");
+        result.bound = Bound::Lower;
+        assert!(info_line(&SearchInfo::Iteration(&result)).starts_with("info depth 6 seldepth 11 score cp 35 lowerbound nodes "));
+        result.bound = Bound::Upper;
+        result.pv.clear();
+        assert!(info_line(&SearchInfo::Iteration(&result)).ends_with(" upperbound nodes 50000 nps 200000 time 250 hashfull 12"));
+
+        assert_eq!(info_line(&SearchInfo::CurrentMove { depth: 9, mv: e2e4, number: 3 }),
+            "info depth 9 currmove e2e4 currmovenumber 3");
+        assert_eq!(info_line(&SearchInfo::Progress { nodes: 1_000, time: Duration::ZERO, hashfull: 0 }),
+            "info nodes 1000 nps 1000000 time 0 hashfull 0");
+    }
+
+    #[test]
+    fn test_best_move_response_reports_mate_score() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let response = best_move_response(&board, &mut Searcher::with_hash_size(1), 3, None);
+        assert!(response.contains("score mate 1 nodes "), "Got {}", response);
+        assert!(response.ends_with("pv a1a8\nbestmove a1a8"), "Got {}", response);
+    }
+
+    #[test]
+    fn test_setoption() {
+        let mut engine = engine();
+        let mut set = |command: &str| engine.handle(command).join("\n");
+        assert_eq!(set("setoption name Hash value 1"), "");
+        assert_eq!(set("setoption name hash value 8"), "");
+        assert_eq!(set("setoption name Hash value 0"), "info string Hash must be between 1 and 1024");
+        assert_eq!(set("setoption name Hash value lots"), "info string Hash must be between 1 and 1024");
+        assert_eq!(set("setoption name Hash"), "info string Hash must be between 1 and 1024");
+        assert_eq!(set("setoption name Contempt Factor value 3"), "info string unknown option: Contempt Factor");
+        assert_eq!(set("setoption Hash value 4"), "info string setoption needs a name");
+        assert_eq!(set("setoption name Move Overhead value 30"), "");
+        assert_eq!(set("setoption name Move Overhead value 5001"), "info string Move Overhead must be between 0 and 5000");
+        assert_eq!(set("setoption name Clear Hash"), "");
+        assert_eq!(engine.options().spin(HASH), Some(8));
+        assert_eq!(engine.options().spin(MOVE_OVERHEAD), Some(30));
     }
 
     #[test]
-    fn test_handle_uci_position() {
-        assert_eq!(handle_uci_command("position startpos moves e2e4"), "position set");
+    fn test_best_move_response_with_time_budget() {
+        let budget = TimeBudget { soft: Duration::from_millis(30), hard: Duration::from_millis(60) };
+        let start = Instant::now();
+        let response = best_move_response(&get_starting_board(), &mut Searcher::with_hash_size(1), MAX_PLY as u32, Some(budget));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(response.lines().last().unwrap().starts_with("bestmove "));
     }
 
     #[test]
-    fn test_handle_uci_go() {
-        handle_uci_command("position startpos moves e2e4"); // Set position
-        let response = handle_uci_command("go");
-        assert!(response.starts_with("bestmove"), "Response should start with 'bestmove'");
+    fn test_perft_response() {
+        let lines = perft_response(&get_starting_board(), 2);
+        assert_eq!(lines.len(), 23);
+        assert!(lines.contains(&"e2e4: 20".to_string()));
+        assert_eq!(lines[21], "Nodes searched: 400");
+        assert_eq!(lines[22], "Captures: 0 En passant: 0 Castles: 0 Promotions: 0 Checks: 0 Checkmates: 0");
     }
 
     #[test]
-    fn test_handle_uci_stop() {
-        assert_eq!(handle_uci_command("stop"), "calculation stopped");
+    fn test_debug_diagnostics() {
+        let mut engine = engine();
+        assert_eq!(engine.handle("position startpos"), vec!["position set"]);
+        assert!(engine.handle("go perft 1").iter().all(|line| !line.starts_with("info string")));
+
+        assert!(engine.handle("debug on").is_empty());
+        assert_eq!(engine.handle("position startpos moves e2e4"), vec![
+            "position set",
+            "info string position rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        ]);
+        assert_eq!(engine.handle("setoption name Move Overhead value 20"), vec!["info string Move Overhead set to 20"]);
+        assert_eq!(engine.handle("go movetime 100"), vec!["info string searching to depth 128 in 80 ms, 80 ms at most"]);
+        engine.handle("stop");
+
+        assert!(engine.handle("debug off").is_empty());
+        assert_eq!(engine.handle("position startpos"), vec!["position set"]);
+    }
+
+    #[test]
+    fn test_logs_the_conversation() {
+        let path = std::env::temp_dir().join(format!("chesslib_uci_log_test_{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let (sender, receiver) = mpsc::channel();
+        let mut engine = engine();
+        engine.set_search_output(Some(sender));
+
+        assert!(engine.handle(&format!("setoption name Debug Log File value {}", path)).is_empty());
+        assert_eq!(engine.options().string(DEBUG_LOG_FILE), Some(path));
+        engine.handle("isready");
+        // The position is logged whether or not debugging is on
+        engine.handle("position startpos moves e2e4");
+        engine.handle("go depth 1");
+        let bestmove = receiver.iter().find(|line| line.starts_with("bestmove")).unwrap();
+        engine.handle("stop");
+        engine.handle("setoption name Debug Log File value <empty>");
+        engine.handle("isready");
+
+        let log = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let messages: Vec<&str> = log.lines().map(|line| &line[24..]).filter(|line| !line.starts_with("<< info")).collect();
+        assert_eq!(messages, vec![
+            ">> isready",
+            "<< readyok",
+            ">> position startpos moves e2e4",
+            "-- Position: rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "<< position set",
+            ">> go depth 1",
+            &format!("<< {}", bestmove),
+            ">> stop",
+            ">> setoption name Debug Log File value <empty>",
+        ]);
     }
 
     #[test]
-    fn test_position_startpos_resets_board() {
-        // Make some moves
-        handle_uci_command("position startpos moves e2e4 e7e5");
-        
-        // Start a new position - this should reset
-        handle_uci_command("position startpos moves d2d4");
-        
-        // Get next move - should be Black to move after d2d4
-        let response = handle_uci_command("go");
-        assert!(response.starts_with("bestmove"));
-        let black_move = response.split_whitespace().nth(1).unwrap();
+    fn test_log_file_that_cannot_be_opened() {
+        let mut engine = engine();
+        assert_eq!(engine.handle("setoption name Debug Log File value /nonexistent/directory/engine.log"),
+            vec!["info string cannot open log file /nonexistent/directory/engine.log: No such file or directory (os error 2)"]);
+        assert_eq!(engine.options().string(DEBUG_LOG_FILE), Some(""));
+        assert!(engine.set_log_file("/nonexistent/directory/engine.log").is_err());
+    }
 
-        // First character should be either a pawn move from rank 7 or a knight move from rank 8
-        let rank = black_move.chars().nth(1).unwrap();
-        assert!(rank == '7' || rank == '8', "Should be Black's move from rank 7 (pawn) or rank 8 (knight)");
+    #[test]
+    fn test_handle_go_perft() {
+        let mut engine = engine();
+        assert_eq!(engine.handle("go perft"), vec!["info string go perft needs a depth"]);
+        assert!(engine.handle("go perft 1").contains(&"Nodes searched: 20".to_string()));
+    }
+
+    #[test]
+    fn test_current_fen() {
+        let mut engine = engine();
+        assert_eq!(engine.fen(), None);
+        engine.handle("position startpos");
+        assert_eq!(engine.fen(), Some(get_starting_board().to_fen()));
+    }
+
+    #[test]
+    fn test_handle_position_invalid_fen() {
+        let mut engine = engine();
+        let response = engine.handle("position fen 4k3/8/8 w - - 0 1");
+        assert!(response[0].starts_with("info string invalid fen"), "Got {:?}", response);
+        // The position is left as it was
+        assert_eq!(engine.fen(), None);
     }
 }
@@ -0,0 +1,159 @@
+use lazy_static::lazy_static;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::board::{Board, Color, Piece};
+
+// Fixed seed so keys, and therefore hashes, are the same from one run to the next
+const ZOBRIST_SEED: u64 = 0x5A0B_2157_C0DE_F00D;
+
+const ALL_PIECES: [Piece; 12] = [
+    Piece::WhitePawn, Piece::WhiteRook, Piece::WhiteKnight, Piece::WhiteBishop, Piece::WhiteQueen, Piece::WhiteKing,
+    Piece::BlackPawn, Piece::BlackRook, Piece::BlackKnight, Piece::BlackBishop, Piece::BlackQueen, Piece::BlackKing,
+];
+
+struct ZobristKeys {
+    pieces: [[u64; 64]; 12],
+    black_to_move: u64,
+    castling: [u64; 16],
+    en_passant_file: [u64; 8],
+}
+
+lazy_static! {
+    static ref KEYS: ZobristKeys = {
+        let mut rng = StdRng::seed_from_u64(ZOBRIST_SEED);
+        let mut keys = ZobristKeys {
+            pieces: [[0; 64]; 12],
+            black_to_move: 0,
+            castling: [0; 16],
+            en_passant_file: [0; 8],
+        };
+        for piece_keys in keys.pieces.iter_mut() {
+            for key in piece_keys.iter_mut() {
+                *key = rng.gen();
+            }
+        }
+        keys.black_to_move = rng.gen();
+        for key in keys.castling.iter_mut() {
+            *key = rng.gen();
+        }
+        for key in keys.en_passant_file.iter_mut() {
+            *key = rng.gen();
+        }
+        keys
+    };
+}
+
+/// Key for a piece standing on the square with the given bit index
+pub fn piece_key(piece: Piece, square: u8) -> u64 {
+    KEYS.pieces[piece as usize][square as usize]
+}
+
+/// Key toggled whenever the side to move changes
+pub fn side_key() -> u64 {
+    KEYS.black_to_move
+}
+
+/// Key for a combination of castling rights flags
+pub fn castling_key(castling_rights: u8) -> u64 {
+    KEYS.castling[(castling_rights & 0xF) as usize]
+}
+
+/// Key for an en passant square on the given file
+pub fn en_passant_key(file: u8) -> u64 {
+    KEYS.en_passant_file[file as usize]
+}
+
+impl Board {
+    /// Computes the Zobrist key of the position from scratch
+    ///
+    /// `apply_move` keeps `Board::hash` up to date incrementally; this is the reference it is
+    /// checked against in debug builds.
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+        for piece in ALL_PIECES {
+            let mut pieces = self.piece_bitboard(&piece);
+            while pieces != 0 {
+                hash ^= piece_key(piece, pieces.trailing_zeros() as u8);
+                pieces &= pieces - 1;
+            }
+        }
+        if self.side_to_move == Color::Black {
+            hash ^= side_key();
+        }
+        hash ^= castling_key(self.castling_rights);
+        if let Some(square) = self.en_passant {
+            hash ^= en_passant_key(square.file());
+        }
+        hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{get_starting_board, STARTING_FEN};
+
+    fn hash_after(fen: &str, moves: &[&str]) -> u64 {
+        let mut board = Board::from_fen(fen).unwrap();
+        board.apply_moves_from_strings(moves.iter().map(|m| m.to_string())).unwrap();
+        assert_eq!(board.hash, board.compute_hash());
+        board.hash
+    }
+
+    #[test]
+    fn test_starting_board_matches_fen() {
+        assert_eq!(get_starting_board().hash, Board::from_fen(STARTING_FEN).unwrap().hash);
+    }
+
+    #[test]
+    fn test_transposition_gives_same_hash() {
+        let a = hash_after(STARTING_FEN, &["g1f3", "g8f6", "b1c3", "b8c6"]);
+        let b = hash_after(STARTING_FEN, &["b1c3", "b8c6", "g1f3", "g8f6"]);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_round_trip_knight_moves_restore_hash() {
+        let a = hash_after(STARTING_FEN, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert_eq!(a, get_starting_board().hash);
+    }
+
+    #[test]
+    fn test_side_castling_and_en_passant_change_hash() {
+        let base = Board::from_fen("r3k2r/8/8/8/4p3/8/3P4/R3K2R w KQkq - 0 1").unwrap().hash;
+        let black = Board::from_fen("r3k2r/8/8/8/4p3/8/3P4/R3K2R b KQkq - 0 1").unwrap().hash;
+        let no_castling = Board::from_fen("r3k2r/8/8/8/4p3/8/3P4/R3K2R w Kkq - 0 1").unwrap().hash;
+        assert_ne!(base, black);
+        assert_ne!(base, no_castling);
+
+        // The same placement reached with and without an en passant square differs
+        let with_ep = hash_after("r3k2r/8/8/8/4p3/8/3P4/R3K2R w KQkq - 0 1", &["d2d4"]);
+        let without_ep = Board::from_fen("r3k2r/8/8/8/3Pp3/8/8/R3K2R b KQkq - 0 1").unwrap().hash;
+        assert_ne!(with_ep, without_ep);
+        assert_eq!(with_ep, Board::from_fen("r3k2r/8/8/8/3Pp3/8/8/R3K2R b KQkq d3 0 1").unwrap().hash);
+    }
+
+    #[test]
+    fn test_special_moves_keep_hash_consistent() {
+        // Castling, en passant and underpromotion with capture
+        hash_after("r3k2r/8/8/8/4p3/8/3P4/R3K2R w KQkq - 0 1", &["e1g1", "e8c8", "d2d4", "e4d3"]);
+        hash_after("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", &["a7b8n"]);
+    }
+
+    #[test]
+    fn test_random_games_keep_hash_consistent() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..20 {
+            let mut board = get_starting_board();
+            for _ in 0..80 {
+                let moves = board.legal_moves();
+                if moves.is_empty() {
+                    break;
+                }
+                board.apply_move(&moves[rng.gen_range(0..moves.len())]);
+                assert_eq!(board.hash, board.compute_hash());
+            }
+        }
+    }
+}
@@ -167,7 +167,7 @@ fn test_random_pawn_moves_no_capture() {
             use rand::seq::SliceRandom;
             if let Some(mv) = possible_moves.as_slice().choose(&mut rand::thread_rng()) {
                 println!("Applying move: {}", mv);
-                board.apply_moves_from_strings(std::iter::once(mv.to_string()));
+                board.apply_moves_from_strings(std::iter::once(mv.to_string())).unwrap();
 
                 assert_eq!(board.white_pawns & board.black_pawns, 0, "White and black pawns overlap!");
 
@@ -189,7 +189,7 @@ fn test_invalid_black_move() {
     let mut board = get_starting_board();
 
     // Apply a move for white
-    board.apply_moves_from_strings(std::iter::once("e2e4".to_string()));
+    board.apply_moves_from_strings(std::iter::once("e2e4".to_string())).unwrap();
 
     assert_eq!(board.side_to_move, Color::Black);
 
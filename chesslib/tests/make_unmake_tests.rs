@@ -0,0 +1,62 @@
+extern crate chesslib;
+use chesslib::board::{get_starting_board, Board};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+
+/// Plays a random game from `start`, then takes every move back, checking each position
+/// is restored exactly on the way down
+fn random_game_round_trip(start: Board, rng: &mut StdRng, max_plies: usize) {
+    let mut board = start;
+    let mut history = Vec::new();
+    for _ in 0..max_plies {
+        let moves = board.legal_moves();
+        if moves.is_empty() {
+            break;
+        }
+        let mv = moves[rng.gen_range(0..moves.len())];
+        let before = board.clone();
+        let undo = board.make_move(&mv);
+        history.push((before, undo));
+    }
+    while let Some((before, undo)) = history.pop() {
+        let mv = undo.mv;
+        board.unmake_move(undo);
+        assert_eq!(board, before, "unmake of {} did not restore {}", mv, before);
+    }
+}
+
+#[test]
+fn test_make_unmake_random_games_from_start() {
+    let mut rng = StdRng::seed_from_u64(11);
+    for _ in 0..50 {
+        random_game_round_trip(get_starting_board(), &mut rng, 200);
+    }
+}
+
+#[test]
+fn test_make_unmake_random_games_from_tactical_positions() {
+    let mut rng = StdRng::seed_from_u64(12);
+    for fen in [KIWIPETE, POSITION_4] {
+        for _ in 0..50 {
+            random_game_round_trip(Board::from_fen(fen).unwrap(), &mut rng, 60);
+        }
+    }
+}
+
+#[test]
+fn test_make_unmake_every_move_of_a_position() {
+    let board = Board::from_fen(KIWIPETE).unwrap();
+    let mut scratch = board.clone();
+    for mv in board.legal_moves() {
+        let undo = scratch.make_move(&mv);
+        for reply in scratch.legal_moves() {
+            let reply_undo = scratch.make_move(&reply);
+            scratch.unmake_move(reply_undo);
+        }
+        scratch.unmake_move(undo);
+        assert_eq!(scratch, board, "unmake of {} did not restore the position", mv);
+    }
+}
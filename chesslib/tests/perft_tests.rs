@@ -0,0 +1,106 @@
+extern crate chesslib;
+use chesslib::board::{Board, STARTING_FEN};
+use chesslib::perft::PerftCounts;
+
+// Reference positions and counts from https://www.chessprogramming.org/Perft_Results
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+const POSITION_4_MIRRORED: &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+fn assert_perft(fen: &str, expected: &[u64]) {
+    let board = Board::from_fen(fen).unwrap();
+    for (depth, &nodes) in expected.iter().enumerate() {
+        let depth = depth as u32 + 1;
+        assert_eq!(board.perft(depth), nodes, "perft({}) of {}", depth, fen);
+    }
+}
+
+fn counts(nodes: u64, captures: u64, en_passants: u64, castles: u64, promotions: u64, checks: u64, checkmates: u64) -> PerftCounts {
+    PerftCounts { nodes, captures, en_passants, castles, promotions, checks, checkmates }
+}
+
+#[test]
+fn test_perft_starting_position() {
+    assert_perft(STARTING_FEN, &[20, 400, 8902, 197281]);
+}
+
+#[test]
+fn test_perft_kiwipete() {
+    assert_perft(KIWIPETE, &[48, 2039, 97862]);
+}
+
+#[test]
+fn test_perft_position_3() {
+    assert_perft(POSITION_3, &[14, 191, 2812, 43238]);
+}
+
+#[test]
+fn test_perft_position_4() {
+    assert_perft(POSITION_4, &[6, 264, 9467]);
+    assert_perft(POSITION_4_MIRRORED, &[6, 264, 9467]);
+}
+
+#[test]
+fn test_perft_position_5() {
+    assert_perft(POSITION_5, &[44, 1486, 62379]);
+}
+
+#[test]
+fn test_perft_position_6() {
+    assert_perft(POSITION_6, &[46, 2079, 89890]);
+}
+
+#[test]
+fn test_perft_counts_starting_position() {
+    let board = Board::from_fen(STARTING_FEN).unwrap();
+    assert_eq!(board.perft_counts(3), counts(8902, 34, 0, 0, 0, 12, 0));
+    assert_eq!(board.perft_counts(4), counts(197281, 1576, 0, 0, 0, 469, 8));
+}
+
+#[test]
+fn test_perft_counts_kiwipete() {
+    let board = Board::from_fen(KIWIPETE).unwrap();
+    assert_eq!(board.perft_counts(1), counts(48, 8, 0, 2, 0, 0, 0));
+    assert_eq!(board.perft_counts(2), counts(2039, 351, 1, 91, 0, 3, 0));
+    assert_eq!(board.perft_counts(3), counts(97862, 17102, 45, 3162, 0, 993, 1));
+}
+
+#[test]
+fn test_perft_counts_position_3() {
+    let board = Board::from_fen(POSITION_3).unwrap();
+    assert_eq!(board.perft_counts(2), counts(191, 14, 0, 0, 0, 10, 0));
+    assert_eq!(board.perft_counts(4), counts(43238, 3348, 123, 0, 0, 1680, 17));
+}
+
+#[test]
+fn test_perft_counts_position_4() {
+    let board = Board::from_fen(POSITION_4).unwrap();
+    assert_eq!(board.perft_counts(2), counts(264, 87, 0, 6, 48, 10, 0));
+    assert_eq!(board.perft_counts(3), counts(9467, 1021, 4, 0, 120, 38, 22));
+}
+
+#[test]
+fn test_divide_kiwipete() {
+    let board = Board::from_fen(KIWIPETE).unwrap();
+    let divided = board.divide(2);
+    assert_eq!(divided.len(), 48);
+    assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2039);
+
+    let castle = divided.iter().find(|(mv, _)| mv.to_string() == "e1g1").unwrap();
+    assert_eq!(castle.1, 43);
+}
+
+// Deeper runs take a while in debug builds, run them with `cargo test --release -- --ignored`
+#[test]
+#[ignore]
+fn test_perft_deep() {
+    assert_perft(STARTING_FEN, &[20, 400, 8902, 197281, 4865609]);
+    assert_perft(KIWIPETE, &[48, 2039, 97862, 4085603]);
+    assert_perft(POSITION_3, &[14, 191, 2812, 43238, 674624]);
+    assert_perft(POSITION_4, &[6, 264, 9467, 422333]);
+    assert_perft(POSITION_5, &[44, 1486, 62379, 2103487]);
+    assert_perft(POSITION_6, &[46, 2079, 89890, 3894594]);
+}